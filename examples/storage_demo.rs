@@ -1,20 +1,20 @@
-/// Demonstration of how data is stored in the MPT
-/// Run with: cargo run --example storage_demo
+//! Demonstration of how data is stored in the MPT
+//! Run with: cargo run --example storage_demo
 
-use mpt::MerklePatriciaTrie;
+use mpt::{MerklePatriciaTrie, MptError};
 
-fn main() {
+fn main() -> Result<(), MptError> {
     println!("=== MPT Storage Demonstration ===\n");
 
     let mut trie = MerklePatriciaTrie::new();
     
     println!("1. Empty Trie");
     println!("   Root hash: {}", hex::encode(trie.root_hash()));
-    println!("   (This is hash of empty bytes)\n");
+    println!("   (This is keccak256(rlp(\"\")), the empty trie root)\n");
 
     // Insert first key
     println!("2. Insert 'dog' → 'puppy'");
-    trie.insert(b"dog", b"puppy".to_vec());
+    trie.insert(b"dog", b"puppy".to_vec())?;
     println!("   Root hash: {}", hex::encode(trie.root_hash()));
     println!("   Tree structure: Single Leaf Node");
     println!("   - Path: compact([6,4,6,15,6,7]) = nibbles of 'dog'");
@@ -22,7 +22,7 @@ fn main() {
 
     // Insert related key
     println!("3. Insert 'doge' → 'coin'");
-    trie.insert(b"doge", b"coin".to_vec());
+    trie.insert(b"doge", b"coin".to_vec())?;
     println!("   Root hash: {}", hex::encode(trie.root_hash()));
     println!("   Tree structure: Extension → Branch → ...");
     println!("   - Extension compresses shared 'do' prefix");
@@ -30,15 +30,15 @@ fn main() {
 
     // Verification
     println!("4. Verify Keys Exist");
-    match trie.get(b"dog") {
+    match trie.get(b"dog")? {
         Some(v) => println!("   ✓ 'dog' found: '{}'", String::from_utf8_lossy(&v)),
         None => println!("   ✗ 'dog' not found"),
     }
-    match trie.get(b"doge") {
+    match trie.get(b"doge")? {
         Some(v) => println!("   ✓ 'doge' found: '{}'", String::from_utf8_lossy(&v)),
         None => println!("   ✗ 'doge' not found"),
     }
-    match trie.get(b"cat") {
+    match trie.get(b"cat")? {
         Some(v) => println!("   ✓ 'cat' found: '{}'", String::from_utf8_lossy(&v)),
         None => println!("   ✗ 'cat' not found"),
     }
@@ -46,14 +46,14 @@ fn main() {
 
     // Show how storage works internally
     println!("5. Storage Internals");
-    println!("   Storage is: HashMap<Hash, Node>");
+    println!("   Storage is: a NodeDb (MemoryDb by default)");
     println!("   - Keys are 32-byte hashes of RLP-encoded nodes");
-    println!("   - Values are the actual Node structures");
+    println!("   - Values are the RLP-encoded nodes");
     println!("   - Root hash points to the top node\n");
 
     // Add unrelated key
     println!("6. Insert 'horse' → 'stallion'");
-    trie.insert(b"horse", b"stallion".to_vec());
+    trie.insert(b"horse", b"stallion".to_vec())?;
     println!("   Root hash: {}", hex::encode(trie.root_hash()));
     println!("   Tree now has two branches:");
     println!("   - One for 'do*' keys (dog, doge)");
@@ -62,7 +62,7 @@ fn main() {
     // Show root hash changes with any modification
     println!("7. Root Hash is Cryptographic Commitment");
     let root_before = trie.root_hash();
-    trie.insert(b"dog", b"animal".to_vec());  // Update value
+    trie.insert(b"dog", b"animal".to_vec())?;  // Update value
     let root_after = trie.root_hash();
     println!("   Root before update: {}", hex::encode(root_before));
     println!("   Root after update:  {}", hex::encode(root_after));
@@ -104,6 +104,7 @@ fn main() {
     println!("═══════════════════════════════════════════════════════════════");
     println!("VISUALIZATION: Storage Details");
     println!("═══════════════════════════════════════════════════════════════");
+    trie.commit()?;
     trie.print_storage();
+    Ok(())
 }
-
@@ -0,0 +1,143 @@
+use crate::error::MptError;
+use crate::node::{Hash, EMPTY_ROOT};
+use primitive_types::U256;
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+
+/// Keccak-256 hash of empty code, held by every account without a contract
+pub const EMPTY_CODE_HASH: Hash = [
+    0xc5, 0xd2, 0x46, 0x01, 0x86, 0xf7, 0x23, 0x3c, 0x92, 0x7e, 0x7d, 0xb2, 0xdc, 0xc7, 0x03, 0xc0,
+    0xe5, 0x00, 0xb6, 0x53, 0xca, 0x82, 0x27, 0x3b, 0x7b, 0xfa, 0xd8, 0x04, 0x5d, 0x85, 0xa4, 0x70,
+];
+
+/// An Ethereum account as stored in the state trie
+///
+/// Encoded as the RLP list `[nonce, balance, storage_root, code_hash]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Account {
+    /// Number of transactions sent (or contracts created) by the account
+    pub nonce: u64,
+    /// Balance in wei
+    pub balance: U256,
+    /// Root hash of the account's storage trie
+    pub storage_root: Hash,
+    /// Keccak-256 hash of the account's code
+    pub code_hash: Hash,
+}
+
+impl Account {
+    /// Creates an account without storage or code
+    pub fn new(nonce: u64, balance: U256) -> Self {
+        Self { nonce, balance, ..Self::default() }
+    }
+
+    /// Returns true if the account is empty in the sense of EIP-161: no
+    /// nonce, no balance and no code
+    pub fn is_empty(&self) -> bool {
+        self.nonce == 0 && self.balance.is_zero() && self.code_hash == EMPTY_CODE_HASH
+    }
+
+    /// Returns the RLP encoding of the account
+    pub fn encode(&self) -> Vec<u8> {
+        rlp::encode(self).to_vec()
+    }
+
+    /// Decodes an account from its RLP encoding
+    pub fn decode(data: &[u8]) -> Result<Self, MptError> {
+        Ok(rlp::decode(data)?)
+    }
+}
+
+impl Default for Account {
+    fn default() -> Self {
+        Self {
+            nonce: 0,
+            balance: U256::zero(),
+            storage_root: EMPTY_ROOT,
+            code_hash: EMPTY_CODE_HASH,
+        }
+    }
+}
+
+impl Encodable for Account {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(4);
+        s.append(&self.nonce);
+        s.append(&self.balance);
+        s.append(&self.storage_root.as_slice());
+        s.append(&self.code_hash.as_slice());
+    }
+}
+
+impl Decodable for Account {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 4 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        Ok(Self {
+            nonce: rlp.val_at(0)?,
+            balance: rlp.val_at(1)?,
+            storage_root: decode_hash(&rlp.at(2)?)?,
+            code_hash: decode_hash(&rlp.at(3)?)?,
+        })
+    }
+}
+
+/// Helper: decodes a 32-byte hash field
+fn decode_hash(rlp: &Rlp) -> Result<Hash, DecoderError> {
+    rlp.decoder().decode_value(|bytes| {
+        bytes.try_into().map_err(|_| DecoderError::Custom("Invalid hash length"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::keccak256;
+
+    #[test]
+    fn test_empty_code_hash() {
+        assert_eq!(EMPTY_CODE_HASH, keccak256(&[]));
+    }
+
+    #[test]
+    fn test_empty_account_encoding() {
+        let account = Account::default();
+        assert!(account.is_empty());
+
+        let encoded = account.encode();
+        let expected = [
+            &[0xf8, 0x44, 0x80, 0x80, 0xa0][..],
+            &EMPTY_ROOT,
+            &[0xa0],
+            &EMPTY_CODE_HASH,
+        ]
+        .concat();
+        assert_eq!(encoded, expected);
+        assert_eq!(Account::decode(&encoded), Ok(account));
+    }
+
+    #[test]
+    fn test_account_roundtrip() {
+        let account = Account {
+            nonce: 5,
+            balance: U256::from_dec_str("1000000000000000000000").unwrap(),
+            storage_root: [0x11; 32],
+            code_hash: [0x22; 32],
+        };
+        assert!(!account.is_empty());
+        assert_eq!(Account::decode(&account.encode()), Ok(account));
+    }
+
+    #[test]
+    fn test_invalid_account_rejected() {
+        // Three fields instead of four
+        let mut stream = RlpStream::new_list(3);
+        stream.append(&1u64).append(&2u64).append(&EMPTY_ROOT.as_slice());
+        assert!(matches!(Account::decode(&stream.out()), Err(MptError::InvalidEncoding(_))));
+
+        // A truncated storage root
+        let mut stream = RlpStream::new_list(4);
+        stream.append(&1u64).append(&2u64).append(&[0u8; 31].as_slice()).append(&EMPTY_CODE_HASH.as_slice());
+        assert!(matches!(Account::decode(&stream.out()), Err(MptError::InvalidEncoding(_))));
+    }
+}
@@ -0,0 +1,267 @@
+use crate::db::NodeDb;
+use crate::error::MptError;
+use crate::nibbles::{bytes_to_nibbles, compact_decode};
+use crate::node::{Link, TrieNode};
+use crate::trie::{MerklePatriciaTrie, dirty};
+use std::collections::BTreeMap;
+
+/// A single write in a batch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+    /// Inserts or replaces the value of a key
+    Insert(Vec<u8>, Vec<u8>),
+    /// Removes a key (a no-op if it is absent)
+    Delete(Vec<u8>),
+}
+
+/// A batch operation on a nibble path: the value to store, or `None` to delete
+type PathOp = (Vec<u8>, Option<Vec<u8>>);
+
+impl<D: NodeDb> MerklePatriciaTrie<D> {
+    /// Applies a set of inserts and deletes in a single pass over the trie
+    ///
+    /// Operations are sorted by key and applied together, so each node on
+    /// the touched paths is rewritten once however many keys run through it.
+    /// When a key appears more than once, its last operation wins. The batch
+    /// is all-or-nothing: if any node cannot be loaded the trie is unchanged.
+    pub fn apply_batch(&mut self, ops: impl IntoIterator<Item = Op>) -> Result<(), MptError> {
+        let ops: BTreeMap<Vec<u8>, Option<Vec<u8>>> = ops
+            .into_iter()
+            .map(|op| match op {
+                Op::Insert(key, value) => (key, Some(value)),
+                Op::Delete(key) => (key, None),
+            })
+            .collect();
+        // Byte order and nibble order agree, so the paths stay sorted
+        let ops: Vec<PathOp> = ops.into_iter().map(|(key, value)| (bytes_to_nibbles(&key), value)).collect();
+
+        let root = self.root_node()?;
+        if let Some(new_root) = self.apply_at(root, 0, &ops)? {
+            self.set_root(new_root);
+        }
+        Ok(())
+    }
+
+    /// Helper: applies `ops`, whose paths all run through `node` at nibble
+    /// `depth`, and returns the updated node, or `None` if nothing changed
+    fn apply_at(&self, node: TrieNode, depth: usize, ops: &[PathOp]) -> Result<Option<TrieNode>, MptError> {
+        match ops {
+            [] => return Ok(None),
+            [(path, Some(value))] => return self.insert_at(&path[depth..], value.clone(), node).map(Some),
+            [(path, None)] => return self.delete_at(&path[depth..], node),
+            _ => {}
+        }
+
+        // Several paths: open the node up as a branch at this depth, apply
+        // each nibble's group below it, then normalize the result
+        match node {
+            TrieNode::Empty => self.apply_to_branch(Box::default(), None, depth, ops),
+
+            TrieNode::Leaf(encoded_path, value) => {
+                let (leaf_path, _) = compact_decode(&encoded_path)?;
+                let mut children: Box<[Option<Link>; 16]> = Box::default();
+                match leaf_path.split_first() {
+                    Some((&idx, rest)) => {
+                        children[idx as usize] = Some(dirty(TrieNode::new_leaf(rest, value)));
+                        self.apply_to_branch(children, None, depth, ops)
+                    }
+                    None => self.apply_to_branch(children, Some(value), depth, ops),
+                }
+            }
+
+            TrieNode::Extension(encoded_path, child) => {
+                let (ext_path, _) = compact_decode(&encoded_path)?;
+                if ops.iter().all(|(path, _)| path[depth..].starts_with(&ext_path)) {
+                    // Every path continues below the extension
+                    let child_node = self.resolve(&child)?;
+                    return match self.apply_at(child_node, depth + ext_path.len(), ops)? {
+                        Some(new_child) => self.prepend_path(&ext_path, new_child).map(Some),
+                        None => Ok(None),
+                    };
+                }
+                let rest = if ext_path.len() == 1 {
+                    child
+                } else {
+                    dirty(TrieNode::new_extension(&ext_path[1..], child))
+                };
+                let mut children: Box<[Option<Link>; 16]> = Box::default();
+                children[ext_path[0] as usize] = Some(rest);
+                self.apply_to_branch(children, None, depth, ops)
+            }
+
+            TrieNode::Branch(children, value) => self.apply_to_branch(children, value, depth, ops),
+        }
+    }
+
+    /// Helper: applies sorted `ops` to a branch at nibble `depth`, one group
+    /// of paths per child slot
+    ///
+    /// Returns `None` if no op changed anything, such as when every op
+    /// deletes an absent key.
+    fn apply_to_branch(
+        &self,
+        mut children: Box<[Option<Link>; 16]>,
+        mut value: Option<Vec<u8>>,
+        depth: usize,
+        mut ops: &[PathOp],
+    ) -> Result<Option<TrieNode>, MptError> {
+        let mut changed = false;
+        // A path ending here sorts before every path continuing below
+        if let Some(((path, op_value), rest)) = ops.split_first()
+            && path.len() == depth
+        {
+            changed = value.is_some() || op_value.is_some();
+            value = op_value.clone();
+            ops = rest;
+        }
+
+        while let Some((path, _)) = ops.first() {
+            let idx = path[depth] as usize;
+            let len = ops.iter().take_while(|(path, _)| path[depth] as usize == idx).count();
+            let (group, rest) = ops.split_at(len);
+
+            let child_node = match &children[idx] {
+                Some(child) => self.resolve(child)?,
+                None => TrieNode::Empty,
+            };
+            if let Some(new_child) = self.apply_at(child_node, depth + 1, group)? {
+                children[idx] = (new_child != TrieNode::Empty).then(|| dirty(new_child));
+                changed = true;
+            }
+            ops = rest;
+        }
+
+        if !changed {
+            return Ok(None);
+        }
+        self.normalize_branch(TrieNode::Branch(children, value)).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDb;
+    use crate::node::{Node, EMPTY_ROOT, keccak256};
+    use crate::test_utils::XorShift;
+
+    /// Applies `ops` one at a time, for comparison with a batch
+    fn apply_sequentially(trie: &mut MerklePatriciaTrie, ops: &[Op]) {
+        for op in ops {
+            match op {
+                Op::Insert(key, value) => trie.insert(key, value.clone()).unwrap(),
+                Op::Delete(key) => trie.delete(key).unwrap(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_batch_matches_sequential_writes() {
+        let mut rng = XorShift(0x5eed_ba7c);
+        let mut batched = MerklePatriciaTrie::new();
+        let mut sequential = MerklePatriciaTrie::new();
+
+        for round in 0..20 {
+            // Short keys collide often, exercising updates, deletes of
+            // absent keys and keys that are prefixes of others
+            let ops: Vec<Op> = (0..200)
+                .map(|_| {
+                    let len = 1 + (rng.next() % 3) as usize;
+                    let key: Vec<u8> = (0..len).map(|_| (rng.next() % 4) as u8).collect();
+                    if rng.next().is_multiple_of(3) {
+                        Op::Delete(key)
+                    } else {
+                        Op::Insert(key, vec![round as u8; 1 + (rng.next() % 40) as usize])
+                    }
+                })
+                .collect();
+
+            apply_sequentially(&mut sequential, &ops);
+            batched.apply_batch(ops).unwrap();
+            assert_eq!(batched.root_hash(), sequential.root_hash(), "round {}", round);
+        }
+        assert_eq!(
+            batched.iter().collect::<Result<Vec<_>, _>>().unwrap(),
+            sequential.iter().collect::<Result<Vec<_>, _>>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_block_sized_batch() {
+        let ops: Vec<Op> = (0..10_000u32)
+            .map(|i| Op::Insert(keccak256(&i.to_be_bytes()).to_vec(), i.to_be_bytes().to_vec()))
+            .collect();
+        let mut batched = MerklePatriciaTrie::new();
+        batched.apply_batch(ops.clone()).unwrap();
+        let mut sequential = MerklePatriciaTrie::new();
+        apply_sequentially(&mut sequential, &ops);
+        assert_eq!(batched.commit().unwrap(), sequential.root_hash());
+
+        // Deleting everything in one batch empties the trie
+        let deletes = ops.iter().map(|op| match op {
+            Op::Insert(key, _) => Op::Delete(key.clone()),
+            Op::Delete(key) => Op::Delete(key.clone()),
+        });
+        batched.apply_batch(deletes).unwrap();
+        assert_eq!(batched.root_hash(), EMPTY_ROOT);
+    }
+
+    #[test]
+    fn test_last_op_per_key_wins() {
+        let mut trie = MerklePatriciaTrie::new();
+        trie.apply_batch([
+            Op::Insert(b"dog".to_vec(), b"puppy".to_vec()),
+            Op::Insert(b"doge".to_vec(), b"coin".to_vec()),
+            Op::Delete(b"dog".to_vec()),
+            Op::Insert(b"doge".to_vec(), b"wow".to_vec()),
+            Op::Insert(b"do".to_vec(), b"verb".to_vec()),
+        ])
+        .unwrap();
+        assert_eq!(trie.get(b"dog").unwrap(), None);
+        assert_eq!(trie.get(b"doge").unwrap(), Some(b"wow".to_vec()));
+        assert_eq!(trie.get(b"do").unwrap(), Some(b"verb".to_vec()));
+
+        // An empty batch changes nothing
+        let root = trie.root_hash();
+        trie.apply_batch([]).unwrap();
+        assert_eq!(trie.root_hash(), root);
+    }
+
+    #[test]
+    fn test_absent_deletes_leave_trie_clean() {
+        let mut trie = MerklePatriciaTrie::new();
+        for i in 0..50u8 {
+            trie.insert(&[i, i], vec![i; 40]).unwrap();
+        }
+        let root = trie.commit().unwrap();
+
+        let ops = [vec![3, 4], vec![3], vec![7, 7, 7], vec![0xff]].map(Op::Delete);
+        trie.apply_batch(ops).unwrap();
+        assert!(!trie.is_dirty());
+        assert_eq!(trie.root_hash(), root);
+    }
+
+    #[test]
+    fn test_failed_batch_leaves_trie_unchanged() {
+        let mut trie = MerklePatriciaTrie::new();
+        for i in 0..64u8 {
+            trie.insert(&[i], vec![i; 40]).unwrap();
+        }
+        let root = trie.commit().unwrap();
+
+        // Lose the leaf holding key [5], below the branches for its two nibbles
+        let mut db: MemoryDb = trie.db().clone();
+        let leaf = keccak256(&Node::new_leaf(&[], vec![5; 40]).encode_raw());
+        db.remove(&leaf).unwrap();
+        let mut damaged = MerklePatriciaTrie::with_db(db, root);
+
+        let result = damaged.apply_batch([
+            Op::Insert(vec![1], vec![0; 40]),
+            Op::Delete(vec![5]),
+            Op::Insert(vec![9], vec![0; 40]),
+        ]);
+        assert_eq!(result, Err(MptError::MissingNode(leaf)));
+        assert!(!damaged.is_dirty());
+        assert_eq!(damaged.root_hash(), root);
+    }
+}
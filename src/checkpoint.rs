@@ -0,0 +1,189 @@
+use crate::db::NodeDb;
+use crate::error::MptError;
+use crate::node::{Hash, TrieNode};
+use crate::trie::MerklePatriciaTrie;
+use std::sync::Arc;
+
+/// Identifies a checkpoint taken with `MerklePatriciaTrie::checkpoint`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CheckpointId(pub(crate) u64);
+
+/// The trie's root state when a checkpoint was taken
+///
+/// Modified nodes are never changed in place, only replaced, so holding on
+/// to the old root is enough to restore everything below it.
+pub(crate) struct Checkpoint {
+    id: CheckpointId,
+    /// The committed root at the time
+    pub(crate) root: Hash,
+    /// The uncommitted root at the time, if any
+    pub(crate) dirty_root: Option<Arc<TrieNode>>,
+}
+
+impl<D: NodeDb> MerklePatriciaTrie<D> {
+    /// Records the current state so that later writes can be undone
+    ///
+    /// Checkpoints nest: each one covers the writes made after it, up to the
+    /// point it is reverted to or discarded. Taking one copies no nodes.
+    pub fn checkpoint(&mut self) -> CheckpointId {
+        let id = CheckpointId(self.next_checkpoint);
+        self.next_checkpoint += 1;
+        self.checkpoints.push(Checkpoint { id, root: self.root, dirty_root: self.dirty_root.clone() });
+        self.hold(self.root);
+        id
+    }
+
+    /// Undoes every write made since checkpoint `id` was taken
+    ///
+    /// Closes `id` and every checkpoint taken after it. Commits made in the
+    /// meantime are undone too: the restored changes become uncommitted
+    /// again, while the nodes written by those commits stay in storage until
+    /// pruned.
+    pub fn revert_to(&mut self, id: CheckpointId) -> Result<(), MptError> {
+        let position = self.checkpoint_position(id)?;
+        let closed: Vec<_> = self.checkpoints.drain(position..).collect();
+        self.root = closed[0].root;
+        self.dirty_root = closed[0].dirty_root.clone();
+        self.release_checkpoints(closed)
+    }
+
+    /// Keeps every write made since checkpoint `id` was taken
+    ///
+    /// Closes `id` and every checkpoint taken after it; writes they covered
+    /// remain covered by any enclosing checkpoint.
+    pub fn discard(&mut self, id: CheckpointId) -> Result<(), MptError> {
+        let position = self.checkpoint_position(id)?;
+        let closed: Vec<_> = self.checkpoints.drain(position..).collect();
+        self.release_checkpoints(closed)
+    }
+
+    /// Helper: drops the references closed checkpoints held on their roots
+    fn release_checkpoints(&mut self, closed: Vec<Checkpoint>) -> Result<(), MptError> {
+        for checkpoint in closed {
+            self.release(checkpoint.root)?;
+        }
+        Ok(())
+    }
+
+    /// Helper: finds an open checkpoint by id
+    fn checkpoint_position(&self, id: CheckpointId) -> Result<usize, MptError> {
+        self.checkpoints
+            .iter()
+            .position(|checkpoint| checkpoint.id == id)
+            .ok_or(MptError::UnknownCheckpoint(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::EMPTY_ROOT;
+    use crate::version::Retention;
+
+    #[test]
+    fn test_nested_revert() {
+        let mut trie = MerklePatriciaTrie::new();
+        trie.insert(b"alice", vec![100; 40]).unwrap();
+        let before_call = trie.root_hash();
+
+        let outer = trie.checkpoint();
+        trie.insert(b"alice", vec![90; 40]).unwrap();
+        trie.insert(b"bob", vec![10; 40]).unwrap();
+        let after_outer_writes = trie.root_hash();
+
+        // A failing sub-call is rolled back on its own
+        let inner = trie.checkpoint();
+        trie.delete(b"alice").unwrap();
+        trie.insert(b"carol", vec![1; 40]).unwrap();
+        trie.revert_to(inner).unwrap();
+        assert_eq!(trie.root_hash(), after_outer_writes);
+        assert_eq!(trie.get(b"carol").unwrap(), None);
+        assert_eq!(trie.get(b"alice").unwrap(), Some(vec![90; 40]));
+
+        // A successful sub-call is folded into the enclosing frame
+        let inner = trie.checkpoint();
+        trie.insert(b"dave", vec![2; 40]).unwrap();
+        trie.discard(inner).unwrap();
+        assert_eq!(trie.get(b"dave").unwrap(), Some(vec![2; 40]));
+
+        trie.revert_to(outer).unwrap();
+        assert_eq!(trie.root_hash(), before_call);
+        assert_eq!(trie.get(b"bob").unwrap(), None);
+        assert_eq!(trie.get(b"dave").unwrap(), None);
+    }
+
+    #[test]
+    fn test_closed_checkpoints_are_unknown() {
+        let mut trie = MerklePatriciaTrie::new();
+        let outer = trie.checkpoint();
+        let inner = trie.checkpoint();
+        trie.insert(b"key", b"value".to_vec()).unwrap();
+
+        // Reverting the outer checkpoint closes the inner one as well
+        trie.revert_to(outer).unwrap();
+        assert_eq!(trie.root_hash(), EMPTY_ROOT);
+        assert_eq!(trie.revert_to(inner), Err(MptError::UnknownCheckpoint(inner)));
+        assert_eq!(trie.discard(outer), Err(MptError::UnknownCheckpoint(outer)));
+
+        // Identifiers are never reused
+        assert_ne!(trie.checkpoint(), outer);
+    }
+
+    #[test]
+    fn test_revert_across_commit_and_prune() {
+        let mut trie = MerklePatriciaTrie::new();
+        for i in 0..32u8 {
+            trie.insert(&[i], vec![i; 40]).unwrap();
+        }
+        let committed = trie.commit().unwrap();
+        trie.insert(&[40], vec![40; 40]).unwrap();
+        let uncommitted = trie.root_hash();
+
+        let checkpoint = trie.checkpoint();
+        for i in 0..32u8 {
+            trie.insert(&[i], vec![0; 40]).unwrap();
+        }
+        trie.commit().unwrap();
+        trie.prune(&[]).unwrap();
+
+        // The checkpoint keeps its nodes alive and its changes uncommitted
+        trie.revert_to(checkpoint).unwrap();
+        assert!(trie.is_dirty());
+        assert_eq!(trie.root_hash(), uncommitted);
+        assert_eq!(trie.get(&[7]).unwrap(), Some(vec![7; 40]));
+        assert_eq!(trie.commit().unwrap(), uncommitted);
+        assert_eq!(trie.get_at_root(committed, &[40]), Ok(None));
+    }
+
+    #[test]
+    fn test_revert_across_dropped_versions() {
+        let mut trie = MerklePatriciaTrie::new();
+        trie.set_retention(Retention::Latest(1)).unwrap();
+        for i in 0..32u8 {
+            trie.insert(&[i], vec![i; 40]).unwrap();
+        }
+        trie.commit_version(1).unwrap();
+        trie.insert(&[40], vec![40; 40]).unwrap();
+        let uncommitted = trie.root_hash();
+
+        // Versions dropped while the checkpoint is open keep its nodes
+        let checkpoint = trie.checkpoint();
+        for block in 2..5u8 {
+            for i in 0..32u8 {
+                trie.insert(&[i], vec![block; 40]).unwrap();
+            }
+            trie.commit_version(block.into()).unwrap();
+        }
+        trie.revert_to(checkpoint).unwrap();
+        assert_eq!(trie.commit().unwrap(), uncommitted);
+        assert_eq!(trie.get(&[7]).unwrap(), Some(vec![7; 40]));
+
+        // Closing it releases them once no version refers to them either
+        let checkpoint = trie.checkpoint();
+        trie.insert(&[7], vec![0; 40]).unwrap();
+        let latest = trie.commit_version(5).unwrap();
+        trie.discard(checkpoint).unwrap();
+        assert!(matches!(trie.get_at_root(uncommitted, &[7]), Err(MptError::MissingNode(_))));
+        assert_eq!(trie.get_at_root(latest, &[7]), Ok(Some(vec![0; 40])));
+    }
+}
@@ -0,0 +1,454 @@
+use crate::error::MptError;
+use crate::node::{Hash, keccak256};
+use crate::proof::MerkleProof;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Storage backend for trie nodes
+///
+/// Nodes are content-addressed: each entry maps the Keccak-256 hash of a
+/// node's RLP encoding to the encoding itself. Implementations can keep nodes
+/// in memory, on disk, or serve a fixed set of nodes such as a proof.
+///
+/// Backend failures are reported as `MptError::StorageError`; a node that is
+/// simply absent is `Ok(None)`.
+pub trait NodeDb {
+    /// Returns the encoded node stored under `hash`, if any
+    fn get(&self, hash: &Hash) -> Result<Option<Vec<u8>>, MptError>;
+
+    /// Stores an encoded node under its hash
+    fn put(&mut self, hash: Hash, encoded: Vec<u8>) -> Result<(), MptError>;
+
+    /// Removes the node stored under `hash`, if any
+    fn remove(&mut self, hash: &Hash) -> Result<(), MptError>;
+
+    /// Returns true if a node is stored under `hash`
+    fn contains(&self, hash: &Hash) -> Result<bool, MptError> {
+        Ok(self.get(hash)?.is_some())
+    }
+
+    /// Returns the hashes of all stored nodes
+    ///
+    /// Used by pruning to find nodes that are no longer reachable.
+    fn hashes(&self) -> Result<Vec<Hash>, MptError>;
+}
+
+/// In-memory node storage backed by a `HashMap`
+///
+/// This is the default backend of `MerklePatriciaTrie`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemoryDb {
+    nodes: HashMap<Hash, Vec<u8>>,
+}
+
+impl MemoryDb {
+    /// Creates an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a store holding the nodes of a proof
+    ///
+    /// A trie opened on this store with the proof's root can answer lookups
+    /// for the proven key without access to the full trie.
+    pub fn from_proof(proof: &MerkleProof) -> Self {
+        let nodes = proof
+            .nodes
+            .iter()
+            .map(|encoded| (keccak256(encoded), encoded.clone()))
+            .collect();
+        Self { nodes }
+    }
+
+    /// Returns the number of stored nodes
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns true if no nodes are stored
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Iterates over all stored `(hash, encoded node)` pairs
+    pub fn iter(&self) -> impl Iterator<Item = (&Hash, &Vec<u8>)> {
+        self.nodes.iter()
+    }
+}
+
+impl NodeDb for MemoryDb {
+    fn get(&self, hash: &Hash) -> Result<Option<Vec<u8>>, MptError> {
+        Ok(self.nodes.get(hash).cloned())
+    }
+
+    fn put(&mut self, hash: Hash, encoded: Vec<u8>) -> Result<(), MptError> {
+        self.nodes.insert(hash, encoded);
+        Ok(())
+    }
+
+    fn remove(&mut self, hash: &Hash) -> Result<(), MptError> {
+        self.nodes.remove(hash);
+        Ok(())
+    }
+
+    fn contains(&self, hash: &Hash) -> Result<bool, MptError> {
+        Ok(self.nodes.contains_key(hash))
+    }
+
+    fn hashes(&self) -> Result<Vec<Hash>, MptError> {
+        Ok(self.nodes.keys().copied().collect())
+    }
+}
+
+/// Length marker of a record that removes a node
+const TOMBSTONE: u32 = u32::MAX;
+
+/// Size of a record header: 32-byte hash followed by a little-endian `u32` length
+const HEADER_LEN: u64 = 36;
+
+/// Persistent node storage in a single append-only file
+///
+/// Every `put` appends a record `hash | len | encoded node`; `remove` appends
+/// a tombstone record with length `u32::MAX`. The in-memory index mapping
+/// hashes to file offsets is rebuilt by reading the file once, front to
+/// back, on `open`, so a trie can be resumed from any root whose nodes were written.
+///
+/// A record cut short by a crash is discarded when the file is reopened.
+#[derive(Debug)]
+pub struct FileDb {
+    path: PathBuf,
+    file: RefCell<File>,
+    /// Offset and length of the latest record for each live node
+    index: HashMap<Hash, (u64, u32)>,
+    /// Offset at which the next record is appended
+    end: u64,
+}
+
+impl FileDb {
+    /// Opens the store at `path`, creating the file if it does not exist
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        let file_len = file.metadata()?.len();
+
+        let mut index = HashMap::new();
+        let mut offset = 0u64;
+        let mut header = [0u8; HEADER_LEN as usize];
+        let mut reader = BufReader::new(&file);
+        while offset + HEADER_LEN <= file_len {
+            reader.read_exact(&mut header)?;
+            let mut hash = [0u8; 32];
+            hash.copy_from_slice(&header[..32]);
+            let len = u32::from_le_bytes(header[32..].try_into().unwrap());
+
+            if len == TOMBSTONE {
+                index.remove(&hash);
+                offset += HEADER_LEN;
+                continue;
+            }
+            if offset + HEADER_LEN + len as u64 > file_len {
+                break;
+            }
+            // Only the headers are needed; skip over the node itself
+            io::copy(&mut (&mut reader).take(len as u64), &mut io::sink())?;
+            index.insert(hash, (offset + HEADER_LEN, len));
+            offset += HEADER_LEN + len as u64;
+        }
+
+        if offset < file_len {
+            // Drop a partially written trailing record
+            file.set_len(offset)?;
+        }
+
+        Ok(Self { path, file: RefCell::new(file), index, end: offset })
+    }
+
+    /// Returns the number of live nodes
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Returns true if no live nodes are stored
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Flushes written records to disk
+    pub fn sync(&self) -> io::Result<()> {
+        self.file.borrow_mut().sync_data()
+    }
+
+    /// Rewrites the file with only the live nodes, reclaiming the space of
+    /// removed and superseded records
+    pub fn compact(&mut self) -> io::Result<()> {
+        let tmp_path = self.path.with_extension("compact");
+        let mut tmp = File::create(&tmp_path)?;
+        let mut index = HashMap::with_capacity(self.index.len());
+        let mut end = 0u64;
+        for (hash, &(offset, len)) in &self.index {
+            let data = self.read_at(offset, len)?;
+            tmp.write_all(hash)?;
+            tmp.write_all(&len.to_le_bytes())?;
+            tmp.write_all(&data)?;
+            index.insert(*hash, (end + HEADER_LEN, len));
+            end += HEADER_LEN + len as u64;
+        }
+        tmp.sync_all()?;
+        drop(tmp);
+
+        std::fs::rename(&tmp_path, &self.path)?;
+        let file = OpenOptions::new().read(true).write(true).open(&self.path)?;
+        self.file = RefCell::new(file);
+        self.index = index;
+        self.end = end;
+        Ok(())
+    }
+
+    /// Returns the current size of the data file in bytes
+    pub fn file_len(&self) -> u64 {
+        self.end
+    }
+
+    fn read_at(&self, offset: u64, len: u32) -> io::Result<Vec<u8>> {
+        let mut file = self.file.borrow_mut();
+        let mut buf = vec![0u8; len as usize];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn append(&mut self, hash: &Hash, len: u32, data: &[u8]) -> io::Result<()> {
+        let mut record = Vec::with_capacity(HEADER_LEN as usize + data.len());
+        record.extend_from_slice(hash);
+        record.extend_from_slice(&len.to_le_bytes());
+        record.extend_from_slice(data);
+
+        let file = self.file.get_mut();
+        file.seek(SeekFrom::Start(self.end))?;
+        file.write_all(&record)?;
+        self.end += record.len() as u64;
+        Ok(())
+    }
+}
+
+impl NodeDb for FileDb {
+    fn get(&self, hash: &Hash) -> Result<Option<Vec<u8>>, MptError> {
+        match self.index.get(hash) {
+            Some(&(offset, len)) => Ok(Some(self.read_at(offset, len)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn put(&mut self, hash: Hash, encoded: Vec<u8>) -> Result<(), MptError> {
+        if self.index.contains_key(&hash) {
+            // Content-addressed: the same hash always holds the same bytes
+            return Ok(());
+        }
+        let len = u32::try_from(encoded.len())
+            .ok()
+            .filter(|&len| len != TOMBSTONE)
+            .ok_or_else(|| MptError::StorageError("node too large for file store".into()))?;
+        let offset = self.end + HEADER_LEN;
+        self.append(&hash, len, &encoded)?;
+        self.index.insert(hash, (offset, len));
+        Ok(())
+    }
+
+    fn remove(&mut self, hash: &Hash) -> Result<(), MptError> {
+        if self.index.contains_key(hash) {
+            self.append(hash, TOMBSTONE, &[])?;
+            self.index.remove(hash);
+        }
+        Ok(())
+    }
+
+    fn contains(&self, hash: &Hash) -> Result<bool, MptError> {
+        Ok(self.index.contains_key(hash))
+    }
+
+    fn hashes(&self) -> Result<Vec<Hash>, MptError> {
+        Ok(self.index.keys().copied().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MerklePatriciaTrie;
+
+    #[test]
+    fn test_memory_db() {
+        let mut db = MemoryDb::new();
+        let hash = keccak256(b"node");
+        assert_eq!(db.contains(&hash), Ok(false));
+
+        db.put(hash, b"node".to_vec()).unwrap();
+        assert_eq!(db.get(&hash), Ok(Some(b"node".to_vec())));
+        assert_eq!(db.len(), 1);
+
+        db.remove(&hash).unwrap();
+        assert!(db.is_empty());
+    }
+
+    /// Returns a fresh path in the system temp directory
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("mpt-{}-{}.db", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_file_db_reopen() {
+        let path = temp_path("reopen");
+        let a = keccak256(b"a");
+        let b = keccak256(b"b");
+        {
+            let mut db = FileDb::open(&path).unwrap();
+            db.put(a, b"node a".to_vec()).unwrap();
+            db.put(b, b"node b".to_vec()).unwrap();
+            db.remove(&a).unwrap();
+        }
+
+        let db = FileDb::open(&path).unwrap();
+        assert_eq!(db.contains(&a), Ok(false));
+        assert_eq!(db.get(&b), Ok(Some(b"node b".to_vec())));
+        assert_eq!(db.len(), 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_file_db_reopen_large_nodes() {
+        let path = temp_path("large");
+        let nodes: Vec<(Hash, Vec<u8>)> =
+            (0..50u32).map(|i| (keccak256(&i.to_be_bytes()), vec![i as u8; 1000 * i as usize])).collect();
+        {
+            let mut db = FileDb::open(&path).unwrap();
+            for (hash, encoded) in &nodes {
+                db.put(*hash, encoded.clone()).unwrap();
+            }
+        }
+
+        // Records far larger than the read buffer are skipped correctly
+        let db = FileDb::open(&path).unwrap();
+        assert_eq!(db.len(), nodes.len());
+        for (hash, encoded) in &nodes {
+            assert_eq!(db.get(hash), Ok(Some(encoded.clone())));
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_file_db_discards_torn_record() {
+        let path = temp_path("torn");
+        let a = keccak256(b"a");
+        {
+            let mut db = FileDb::open(&path).unwrap();
+            db.put(a, b"node a".to_vec()).unwrap();
+        }
+        // Simulate a crash halfway through writing a second record
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[0x11; 32]).unwrap();
+        file.write_all(&100u32.to_le_bytes()).unwrap();
+        file.write_all(b"only").unwrap();
+        drop(file);
+
+        let mut db = FileDb::open(&path).unwrap();
+        assert_eq!(db.len(), 1);
+        let b = keccak256(b"b");
+        db.put(b, b"node b".to_vec()).unwrap();
+        drop(db);
+
+        let db = FileDb::open(&path).unwrap();
+        assert_eq!(db.get(&a), Ok(Some(b"node a".to_vec())));
+        assert_eq!(db.get(&b), Ok(Some(b"node b".to_vec())));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_file_db_compact() {
+        let path = temp_path("compact");
+        let a = keccak256(b"a");
+        let b = keccak256(b"b");
+        let mut db = FileDb::open(&path).unwrap();
+        db.put(a, vec![1; 100]).unwrap();
+        db.put(b, vec![2; 100]).unwrap();
+        db.remove(&a).unwrap();
+        let before = db.file_len();
+
+        db.compact().unwrap();
+        assert!(db.file_len() < before);
+        assert_eq!(db.get(&b), Ok(Some(vec![2; 100])));
+        drop(db);
+
+        let db = FileDb::open(&path).unwrap();
+        assert_eq!(db.len(), 1);
+        assert_eq!(db.get(&b), Ok(Some(vec![2; 100])));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_trie_over_proof_db() {
+        let mut trie = MerklePatriciaTrie::new();
+        for key in [&b"do"[..], b"dog", b"doge", b"horse"] {
+            trie.insert(key, vec![0xAB; 40]).unwrap();
+        }
+        let proof = trie.prove(b"doge").unwrap();
+
+        let light = MerklePatriciaTrie::with_db(MemoryDb::from_proof(&proof), trie.root_hash());
+        assert_eq!(light.get(b"doge"), Ok(Some(vec![0xAB; 40])));
+
+        // Keys outside the proven path are missing, not silently absent
+        assert!(matches!(light.get(b"horse"), Err(MptError::MissingNode(_))));
+    }
+
+    /// A backend that counts writes, standing in for a custom store
+    #[derive(Default)]
+    struct CountingDb {
+        inner: MemoryDb,
+        writes: usize,
+    }
+
+    impl NodeDb for CountingDb {
+        fn get(&self, hash: &Hash) -> Result<Option<Vec<u8>>, MptError> {
+            self.inner.get(hash)
+        }
+
+        fn put(&mut self, hash: Hash, encoded: Vec<u8>) -> Result<(), MptError> {
+            self.writes += 1;
+            self.inner.put(hash, encoded)
+        }
+
+        fn remove(&mut self, hash: &Hash) -> Result<(), MptError> {
+            self.inner.remove(hash)
+        }
+
+        fn hashes(&self) -> Result<Vec<Hash>, MptError> {
+            self.inner.hashes()
+        }
+    }
+
+    #[test]
+    fn test_custom_backend() {
+        let mut trie = MerklePatriciaTrie::with_db(CountingDb::default(), crate::EMPTY_ROOT);
+        trie.insert(b"dog", b"puppy".to_vec()).unwrap();
+        trie.insert(b"doge", b"coin".to_vec()).unwrap();
+
+        assert_eq!(trie.get(b"dog"), Ok(Some(b"puppy".to_vec())));
+        assert_eq!(trie.db().writes, 0);
+        trie.commit().unwrap();
+        assert!(trie.db().writes > 0);
+
+        let mut reference = MerklePatriciaTrie::new();
+        reference.insert(b"dog", b"puppy".to_vec()).unwrap();
+        reference.insert(b"doge", b"coin".to_vec()).unwrap();
+        assert_eq!(trie.root_hash(), reference.root_hash());
+    }
+}
@@ -0,0 +1,256 @@
+use crate::db::NodeDb;
+use crate::error::MptError;
+use crate::iter::TrieIter;
+use crate::nibbles::{compact_decode, nibbles_to_bytes};
+use crate::node::{Hash, Link, TrieNode};
+use crate::trie::{MerklePatriciaTrie, dirty};
+
+/// A difference between two versions of a trie
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// The key exists only in the newer version
+    Added { key: Vec<u8>, value: Vec<u8> },
+    /// The key exists only in the older version
+    Removed { key: Vec<u8>, value: Vec<u8> },
+    /// The key exists in both versions with different values
+    Modified { key: Vec<u8>, old: Vec<u8>, new: Vec<u8> },
+}
+
+impl Change {
+    /// Returns the changed key
+    pub fn key(&self) -> &[u8] {
+        match self {
+            Change::Added { key, .. } | Change::Removed { key, .. } | Change::Modified { key, .. } => key,
+        }
+    }
+}
+
+/// The children of a node as seen from its own path: a value ending here and
+/// a reference per next nibble
+type Expanded = (Option<Vec<u8>>, Box<[Option<Link>; 16]>);
+
+impl<D: NodeDb> MerklePatriciaTrie<D> {
+    /// Returns the changes turning the trie at `old_root` into the trie at
+    /// `new_root`, in lexicographic key order
+    ///
+    /// Both roots must be committed to this trie's node store. The two tries
+    /// are walked in lockstep and any pair of subtries with the same hash is
+    /// skipped without being loaded, so the cost follows the size of the
+    /// change rather than the size of the tries.
+    pub fn diff(&self, old_root: Hash, new_root: Hash) -> TrieDiff<'_, D> {
+        TrieDiff {
+            trie: self,
+            stack: vec![(Vec::new(), Some(Link::stored(old_root)), Some(Link::stored(new_root)))],
+            one_sided: None,
+        }
+    }
+}
+
+/// Iterator over the changes between two roots, created by
+/// `MerklePatriciaTrie::diff`
+///
+/// A node that cannot be loaded is reported as an error and the subtries
+/// below it skipped.
+pub struct TrieDiff<'a, D: NodeDb> {
+    trie: &'a MerklePatriciaTrie<D>,
+    /// Pairs of old and new subtries still to compare, with the nibble path
+    /// leading to both
+    stack: Vec<(Vec<u8>, Option<Link>, Option<Link>)>,
+    /// Entries of a subtrie that exists on one side only, and whether they
+    /// were added
+    one_sided: Option<(TrieIter<'a, D>, bool)>,
+}
+
+impl<D: NodeDb> TrieDiff<'_, D> {
+    /// Helper: compares an old and a new subtrie at `path`, returning the
+    /// change to the value ending there, if any
+    fn compare(&mut self, path: Vec<u8>, old: Option<Link>, new: Option<Link>) -> Result<Option<Change>, MptError> {
+        if old == new {
+            return Ok(None);
+        }
+        let (old, new) = match (old, new) {
+            (Some(old), Some(new)) => (old, new),
+            (None, Some(new)) => {
+                self.one_sided = Some((TrieIter::new(self.trie, path, new), true));
+                return Ok(None);
+            }
+            (Some(old), None) => {
+                self.one_sided = Some((TrieIter::new(self.trie, path, old), false));
+                return Ok(None);
+            }
+            (None, None) => return Ok(None),
+        };
+
+        let (old_value, mut old_children) = self.expand(&old)?;
+        let (new_value, mut new_children) = self.expand(&new)?;
+        // Push in reverse so that child 0 is compared first
+        for idx in (0..16).rev() {
+            if old_children[idx].is_some() || new_children[idx].is_some() {
+                let mut child_path = path.clone();
+                child_path.push(idx as u8);
+                self.stack.push((child_path, old_children[idx].take(), new_children[idx].take()));
+            }
+        }
+
+        Ok(match (old_value, new_value) {
+            (Some(old), Some(new)) if old == new => None,
+            (Some(old), Some(new)) => Some(Change::Modified { key: nibbles_to_bytes(&path)?, old, new }),
+            (None, Some(value)) => Some(Change::Added { key: nibbles_to_bytes(&path)?, value }),
+            (Some(value), None) => Some(Change::Removed { key: nibbles_to_bytes(&path)?, value }),
+            (None, None) => None,
+        })
+    }
+
+    /// Helper: views the node behind `child` as a branch at its own path
+    ///
+    /// Leaves and extensions become a single child one nibble further down,
+    /// so that tries of different shapes can be compared nibble by nibble.
+    fn expand(&self, child: &Link) -> Result<Expanded, MptError> {
+        let mut children: Box<[Option<Link>; 16]> = Box::default();
+        Ok(match self.trie.resolve(child)? {
+            TrieNode::Empty => (None, children),
+            TrieNode::Branch(branch_children, value) => (value, branch_children),
+            TrieNode::Leaf(encoded_path, value) => {
+                let (leaf_path, _) = compact_decode(&encoded_path)?;
+                match leaf_path.split_first() {
+                    Some((&idx, rest)) => {
+                        children[idx as usize] = Some(dirty(TrieNode::new_leaf(rest, value)));
+                        (None, children)
+                    }
+                    None => (Some(value), children),
+                }
+            }
+            TrieNode::Extension(encoded_path, ext_child) => {
+                let (ext_path, _) = compact_decode(&encoded_path)?;
+                children[ext_path[0] as usize] = Some(if ext_path.len() == 1 {
+                    ext_child
+                } else {
+                    dirty(TrieNode::new_extension(&ext_path[1..], ext_child))
+                });
+                (None, children)
+            }
+        })
+    }
+}
+
+impl<D: NodeDb> Iterator for TrieDiff<'_, D> {
+    type Item = Result<Change, MptError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((entries, added)) = &mut self.one_sided {
+                match entries.next() {
+                    Some(Ok((key, value))) if *added => return Some(Ok(Change::Added { key, value })),
+                    Some(Ok((key, value))) => return Some(Ok(Change::Removed { key, value })),
+                    Some(Err(err)) => return Some(Err(err)),
+                    None => self.one_sided = None,
+                }
+            }
+
+            let (path, old, new) = self.stack.pop()?;
+            if let Some(item) = self.compare(path, old, new).transpose() {
+                return Some(item);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDb;
+    use crate::node::{EMPTY_ROOT, keccak256};
+    use std::collections::{BTreeMap, HashSet};
+
+    /// Computes the expected changes by comparing the full contents
+    fn naive_diff(old: &BTreeMap<Vec<u8>, Vec<u8>>, new: &BTreeMap<Vec<u8>, Vec<u8>>) -> Vec<Change> {
+        let keys: std::collections::BTreeSet<_> = old.keys().chain(new.keys()).collect();
+        keys.into_iter()
+            .filter_map(|key| match (old.get(key), new.get(key)) {
+                (Some(o), Some(n)) if o == n => None,
+                (Some(o), Some(n)) => Some(Change::Modified { key: key.clone(), old: o.clone(), new: n.clone() }),
+                (None, Some(n)) => Some(Change::Added { key: key.clone(), value: n.clone() }),
+                (Some(o), None) => Some(Change::Removed { key: key.clone(), value: o.clone() }),
+                (None, None) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_matches_contents() {
+        let mut trie = MerklePatriciaTrie::new();
+        let mut old = BTreeMap::new();
+        for i in 0..300u32 {
+            let key = format!("acct/{}", i * 7).into_bytes();
+            let value = vec![i as u8; 1 + (i % 40) as usize];
+            trie.insert(&key, value.clone()).unwrap();
+            old.insert(key, value);
+        }
+        let old_root = trie.commit().unwrap();
+
+        // Modify, delete and add keys, including keys that are prefixes of
+        // existing ones and keys that reshape extensions
+        let mut new = old.clone();
+        for i in (0..300u32).step_by(13) {
+            let key = format!("acct/{}", i * 7).into_bytes();
+            trie.insert(&key, b"modified".to_vec()).unwrap();
+            new.insert(key, b"modified".to_vec());
+        }
+        for i in (5..300u32).step_by(17) {
+            let key = format!("acct/{}", i * 7).into_bytes();
+            trie.delete(&key).unwrap();
+            new.remove(&key);
+        }
+        for key in [&b"acct/1"[..], b"acct/", b"acct/99999", b"zebra", b"a"] {
+            trie.insert(key, b"added".to_vec()).unwrap();
+            new.insert(key.to_vec(), b"added".to_vec());
+        }
+        let new_root = trie.commit().unwrap();
+
+        let changes: Vec<Change> = trie.diff(old_root, new_root).collect::<Result<_, _>>().unwrap();
+        assert_eq!(changes, naive_diff(&old, &new));
+        let reverse: Vec<Change> = trie.diff(new_root, old_root).collect::<Result<_, _>>().unwrap();
+        assert_eq!(reverse, naive_diff(&new, &old));
+
+        assert_eq!(trie.diff(new_root, new_root).count(), 0);
+        let from_empty: Vec<Change> = trie.diff(EMPTY_ROOT, old_root).collect::<Result<_, _>>().unwrap();
+        assert_eq!(from_empty, naive_diff(&BTreeMap::new(), &old));
+    }
+
+    #[test]
+    fn test_shared_subtries_are_not_loaded() {
+        let mut trie = MerklePatriciaTrie::new();
+        for i in 0..1000u32 {
+            trie.insert(&i.to_be_bytes(), keccak256(&i.to_be_bytes()).to_vec()).unwrap();
+        }
+        let old_root = trie.commit().unwrap();
+        trie.insert(&500u32.to_be_bytes(), vec![0xFF; 40]).unwrap();
+        trie.insert(&5000u32.to_be_bytes(), vec![0xEE; 40]).unwrap();
+        let new_root = trie.commit().unwrap();
+
+        // Drop every node the two versions share; the diff must not need them.
+        // Values are distinct so that no subtrie repeats at another position
+        let reachable = |root: Hash| -> HashSet<Hash> {
+            let mut live = MerklePatriciaTrie::with_db(trie.db().clone(), root);
+            live.prune(&[]).unwrap();
+            live.db().hashes().unwrap().into_iter().collect()
+        };
+        let shared: HashSet<Hash> = reachable(old_root).intersection(&reachable(new_root)).copied().collect();
+        assert!(shared.len() > 50);
+        let mut db: MemoryDb = trie.db().clone();
+        for hash in &shared {
+            db.remove(hash).unwrap();
+        }
+        let damaged = MerklePatriciaTrie::with_db(db, new_root);
+
+        let changes: Vec<Change> = damaged.diff(old_root, new_root).collect::<Result<_, _>>().unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                Change::Modified { key: 500u32.to_be_bytes().to_vec(), old: keccak256(&500u32.to_be_bytes()).to_vec(), new: vec![0xFF; 40] },
+                Change::Added { key: 5000u32.to_be_bytes().to_vec(), value: vec![0xEE; 40] },
+            ]
+        );
+        assert_eq!(changes[1].key(), 5000u32.to_be_bytes());
+    }
+}
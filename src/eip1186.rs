@@ -0,0 +1,425 @@
+use crate::account::{Account, EMPTY_CODE_HASH};
+use crate::error::MptError;
+use crate::node::{Hash, keccak256};
+use crate::proof::{MerkleProof, ProofError, verify_proof};
+use crate::state::Address;
+use primitive_types::U256;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// An account proof in the shape of an EIP-1186 `eth_getProof` response
+///
+/// Serializes to and from the JSON returned by Ethereum clients: byte strings
+/// and hashes as `0x`-prefixed hex, quantities as hex without leading zeros.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountProof {
+    /// The account's address
+    #[serde(with = "hex_fixed")]
+    pub address: Address,
+    /// RLP-encoded state trie nodes from the state root to the account
+    #[serde(with = "hex_list")]
+    pub account_proof: Vec<Vec<u8>>,
+    /// The account's balance
+    #[serde(with = "quantity_u256")]
+    pub balance: U256,
+    /// The account's code hash
+    #[serde(with = "hex_fixed")]
+    pub code_hash: Hash,
+    /// The account's nonce
+    #[serde(with = "quantity_u64")]
+    pub nonce: u64,
+    /// The account's storage root
+    #[serde(with = "hex_fixed")]
+    pub storage_hash: Hash,
+    /// Proofs for the requested storage slots
+    pub storage_proof: Vec<StorageProof>,
+}
+
+/// A storage slot proof within an `AccountProof`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageProof {
+    /// The storage slot, as written in the request
+    pub key: StorageKey,
+    /// The slot's value (zero for an unset slot)
+    #[serde(with = "quantity_u256")]
+    pub value: U256,
+    /// RLP-encoded storage trie nodes from the storage root to the slot
+    #[serde(with = "hex_list")]
+    pub proof: Vec<Vec<u8>>,
+}
+
+/// A storage slot key as written in an `eth_getProof` request
+///
+/// Clients echo keys back as the caller sent them, so shorter (even
+/// odd-length) quantities such as `0x1` are accepted and keep their width
+/// when serialized again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StorageKey {
+    slot: Hash,
+    digits: usize,
+}
+
+impl StorageKey {
+    /// Returns the slot, left-padded to 32 bytes
+    pub fn slot(&self) -> Hash {
+        self.slot
+    }
+}
+
+impl From<Hash> for StorageKey {
+    fn from(slot: Hash) -> Self {
+        StorageKey { slot, digits: 64 }
+    }
+}
+
+impl Serialize for StorageKey {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let hex = hex::encode(self.slot);
+        serializer.serialize_str(&format!("0x{}", &hex[64 - self.digits..]))
+    }
+}
+
+impl<'de> Deserialize<'de> for StorageKey {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        let digits = strip_hex_prefix(&s)?;
+        if digits.is_empty() || digits.len() > 64 {
+            return Err(serde::de::Error::custom("expected 1 to 64 hex digits"));
+        }
+        let mut slot = [0u8; 32];
+        hex::decode_to_slice(format!("{:0>64}", digits), &mut slot).map_err(serde::de::Error::custom)?;
+        Ok(StorageKey { slot, digits: digits.len() })
+    }
+}
+
+impl AccountProof {
+    /// Verifies the account proof against a trusted state root, then each
+    /// storage proof against the proven storage root
+    ///
+    /// The reported account fields must match the proven account exactly. An
+    /// account proven absent must be reported with zero nonce and balance,
+    /// an empty storage root and an empty (or zero) code hash.
+    pub fn verify(&self, state_root: Hash) -> Result<(), AccountProofError> {
+        let proof = MerkleProof::new(self.account_proof.clone());
+        let (proven, code_hash) = match verify_proof(state_root, &keccak256(&self.address), &proof)? {
+            Some(encoded) => (Account::decode(&encoded).map_err(AccountProofError::InvalidValue)?, self.code_hash),
+            // Clients report a zero code hash for missing accounts
+            None if self.code_hash == [0; 32] => (Account::default(), EMPTY_CODE_HASH),
+            None => (Account::default(), self.code_hash),
+        };
+        let reported = Account {
+            nonce: self.nonce,
+            balance: self.balance,
+            storage_root: self.storage_hash,
+            code_hash,
+        };
+        if reported != proven {
+            return Err(AccountProofError::AccountMismatch);
+        }
+
+        for storage in &self.storage_proof {
+            storage.verify(self.storage_hash)?;
+        }
+        Ok(())
+    }
+}
+
+impl StorageProof {
+    /// Verifies the slot proof against the account's storage root
+    pub fn verify(&self, storage_root: Hash) -> Result<(), AccountProofError> {
+        let proof = MerkleProof::new(self.proof.clone());
+        let proven = match verify_proof(storage_root, &keccak256(&self.key.slot), &proof)? {
+            Some(encoded) => rlp::decode(&encoded).map_err(|err| AccountProofError::InvalidValue(err.into()))?,
+            None => U256::zero(),
+        };
+        if proven != self.value {
+            return Err(AccountProofError::StorageMismatch(self.key.slot));
+        }
+        Ok(())
+    }
+}
+
+/// Errors that can occur while verifying an `AccountProof`
+#[derive(Debug, Clone, PartialEq)]
+pub enum AccountProofError {
+    /// The account or a storage proof is incomplete or malformed
+    Proof(ProofError),
+    /// A proven account or slot value cannot be decoded
+    InvalidValue(MptError),
+    /// The reported account fields differ from the proven account
+    AccountMismatch,
+    /// The reported value of this slot differs from the proven value
+    StorageMismatch(Hash),
+}
+
+impl fmt::Display for AccountProofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccountProofError::Proof(err) => write!(f, "invalid proof: {}", err),
+            AccountProofError::InvalidValue(err) => write!(f, "invalid proven value: {}", err),
+            AccountProofError::AccountMismatch => write!(f, "account fields do not match the proof"),
+            AccountProofError::StorageMismatch(key) => {
+                write!(f, "storage slot 0x{} does not match the proof", hex::encode(key))
+            }
+        }
+    }
+}
+
+impl std::error::Error for AccountProofError {}
+
+impl From<ProofError> for AccountProofError {
+    fn from(err: ProofError) -> Self {
+        AccountProofError::Proof(err)
+    }
+}
+
+/// Helper: strips the `0x` prefix required on hex strings
+fn strip_hex_prefix<E: serde::de::Error>(s: &str) -> Result<&str, E> {
+    s.strip_prefix("0x").ok_or_else(|| E::custom("missing 0x prefix"))
+}
+
+/// (De)serializes fixed-length byte arrays as `0x`-prefixed hex
+mod hex_fixed {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer, const N: usize>(bytes: &[u8; N], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("0x{}", hex::encode(bytes)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(deserializer: D) -> Result<[u8; N], D::Error> {
+        let s = String::deserialize(deserializer)?;
+        let bytes = hex::decode(super::strip_hex_prefix(&s)?).map_err(serde::de::Error::custom)?;
+        bytes
+            .try_into()
+            .map_err(|_| serde::de::Error::custom(format!("expected {} bytes", N)))
+    }
+}
+
+/// (De)serializes lists of byte strings as `0x`-prefixed hex
+mod hex_list {
+    use serde::ser::SerializeSeq;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(items: &[Vec<u8>], serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(items.len()))?;
+        for item in items {
+            seq.serialize_element(&format!("0x{}", hex::encode(item)))?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Vec<u8>>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|s| hex::decode(super::strip_hex_prefix(s)?).map_err(serde::de::Error::custom))
+            .collect()
+    }
+}
+
+/// (De)serializes `U256` quantities as hex without leading zeros
+mod quantity_u256 {
+    use primitive_types::U256;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &U256, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:#x}", value))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<U256, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        U256::from_str_radix(super::strip_hex_prefix(&s)?, 16).map_err(serde::de::Error::custom)
+    }
+}
+
+/// (De)serializes `u64` quantities as hex without leading zeros
+mod quantity_u64 {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:#x}", value))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        u64::from_str_radix(super::strip_hex_prefix(&s)?, 16).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::EMPTY_ROOT;
+    use crate::state::StateTrie;
+    use crate::test_utils::slot;
+
+    const ALICE: Address = [0xaa; 20];
+    const BOB: Address = [0xbb; 20];
+
+    fn sample_state() -> StateTrie {
+        let mut state = StateTrie::new();
+        state.set_account(&ALICE, &Account::new(3, U256::from(1_000_000))).unwrap();
+        state.set_account(&BOB, &Account::new(0, U256::from(5))).unwrap();
+        state.set_storage(&ALICE, slot(0), U256::from(0x1234));
+        state.set_storage(&ALICE, slot(1), U256::from(1) << 160);
+        state.commit().unwrap();
+        state
+    }
+
+    #[test]
+    fn test_get_proof_verifies() {
+        let state = sample_state();
+        let root = state.root_hash();
+
+        let proof = state.get_proof(&ALICE, &[slot(0), slot(1), slot(9)]).unwrap();
+        assert_eq!(proof.nonce, 3);
+        assert_eq!(proof.storage_proof[0].value, U256::from(0x1234));
+        assert_eq!(proof.storage_proof[2].value, U256::zero());
+        assert_eq!(proof.verify(root), Ok(()));
+
+        // A missing account is proven absent with empty values
+        let missing = state.get_proof(&[0xcc; 20], &[slot(0)]).unwrap();
+        assert_eq!(missing.storage_hash, EMPTY_ROOT);
+        assert_eq!(missing.code_hash, [0; 32]);
+        assert!(missing.storage_proof[0].proof.is_empty());
+        assert_eq!(missing.verify(root), Ok(()));
+    }
+
+    #[test]
+    fn test_tampered_proof_rejected() {
+        let state = sample_state();
+        let root = state.root_hash();
+        let proof = state.get_proof(&ALICE, &[slot(0)]).unwrap();
+
+        let mut wrong_balance = proof.clone();
+        wrong_balance.balance += U256::one();
+        assert_eq!(wrong_balance.verify(root), Err(AccountProofError::AccountMismatch));
+
+        let mut wrong_value = proof.clone();
+        wrong_value.storage_proof[0].value = U256::from(0x1235);
+        assert_eq!(wrong_value.verify(root), Err(AccountProofError::StorageMismatch(slot(0))));
+
+        // Only a missing account may report a zero code hash
+        let mut zero_code_hash = proof.clone();
+        zero_code_hash.code_hash = [0; 32];
+        assert_eq!(zero_code_hash.verify(root), Err(AccountProofError::AccountMismatch));
+
+        let mut missing_node = proof.clone();
+        missing_node.storage_proof[0].proof.pop();
+        assert!(matches!(missing_node.verify(root), Err(AccountProofError::Proof(_))));
+
+        assert!(matches!(proof.verify([0x11; 32]), Err(AccountProofError::Proof(_))));
+    }
+
+    #[test]
+    fn test_json_shape() {
+        let state = sample_state();
+        let proof = state.get_proof(&BOB, &[slot(1)]).unwrap();
+        let json = serde_json::to_value(&proof).unwrap();
+
+        assert_eq!(json["address"], format!("0x{}", hex::encode(BOB)));
+        assert_eq!(json["balance"], "0x5");
+        assert_eq!(json["nonce"], "0x0");
+        assert_eq!(json["storageHash"], format!("0x{}", hex::encode(EMPTY_ROOT)));
+        assert_eq!(json["codeHash"], format!("0x{}", hex::encode(EMPTY_CODE_HASH)));
+        assert_eq!(json["accountProof"].as_array().unwrap().len(), proof.account_proof.len());
+        assert_eq!(json["storageProof"][0]["key"], format!("0x{}", hex::encode(slot(1))));
+        assert_eq!(json["storageProof"][0]["value"], "0x0");
+        assert!(json["storageProof"][0]["proof"].as_array().unwrap().is_empty());
+
+        let decoded: AccountProof = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded, proof);
+    }
+
+    #[test]
+    fn test_matches_client_response() {
+        // One account holding 1 ether with slot 0 set to 1, answered in the
+        // exact layout clients use: field order, hex quantities without
+        // leading zeros, and zero values and code hash for a missing account
+        let mut state = StateTrie::new();
+        state.set_account(&ALICE, &Account::new(1, U256::from(1_000_000_000_000_000_000u64))).unwrap();
+        state.set_storage(&ALICE, slot(0), U256::one());
+        state.commit().unwrap();
+        let leaf = "0xf872a1205ab9a75647463db7d9263bfdf0f9b455fd5a2ff89f446d3dfa3dfe67cae5649db84ef84c01880de0b6b3a7640000a0821e2556a290c86405f8160a2d662042a431ba456b9db265c79bb837c04be5f0a0c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470";
+
+        let present = format!(
+            concat!(
+                r#"{{"address":"0xaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa","accountProof":["{}"],"#,
+                r#""balance":"0xde0b6b3a7640000","#,
+                r#""codeHash":"0xc5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470","nonce":"0x1","#,
+                r#""storageHash":"0x821e2556a290c86405f8160a2d662042a431ba456b9db265c79bb837c04be5f0","#,
+                r#""storageProof":[{{"key":"0x0000000000000000000000000000000000000000000000000000000000000000","#,
+                r#""value":"0x1","proof":["0xe3a120290decd9548b62a8d60345a988386fc84ba6bc95484008f6362f93160ef3e56301"]}}]}}"#,
+            ),
+            leaf
+        );
+        let missing = format!(
+            concat!(
+                r#"{{"address":"0xbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb","accountProof":["{}"],"balance":"0x0","#,
+                r#""codeHash":"0x0000000000000000000000000000000000000000000000000000000000000000","nonce":"0x0","#,
+                r#""storageHash":"0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421","#,
+                r#""storageProof":[{{"key":"0x0000000000000000000000000000000000000000000000000000000000000000","#,
+                r#""value":"0x0","proof":[]}}]}}"#,
+            ),
+            leaf
+        );
+
+        let root = state.root_hash();
+        for (address, expected) in [(ALICE, present), (BOB, missing)] {
+            let proof = state.get_proof(&address, &[slot(0)]).unwrap();
+            assert_eq!(serde_json::to_string(&proof).unwrap(), expected);
+            let parsed: AccountProof = serde_json::from_str(&expected).unwrap();
+            assert_eq!(parsed, proof);
+            assert_eq!(parsed.verify(root), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_proves_committed_state_only() {
+        let mut state = sample_state();
+        let root = state.root_hash();
+        state.set_account(&BOB, &Account::new(9, U256::from(9))).unwrap();
+        state.set_storage(&ALICE, slot(0), U256::from(7));
+
+        let proof = state.get_proof(&BOB, &[]).unwrap();
+        assert_eq!(proof.nonce, 0);
+        assert_eq!(proof.verify(root), Ok(()));
+        let proof = state.get_proof(&ALICE, &[slot(0)]).unwrap();
+        assert_eq!(proof.storage_proof[0].value, U256::from(0x1234));
+        assert_eq!(proof.verify(root), Ok(()));
+    }
+
+    #[test]
+    fn test_parse_client_response() {
+        // Response shape as returned by clients for an account that does not
+        // exist in an empty state
+        let json = r#"{
+            "address": "0x00000000000000000000000000000000000000aa",
+            "accountProof": [],
+            "balance": "0x0",
+            "codeHash": "0x0000000000000000000000000000000000000000000000000000000000000000",
+            "nonce": "0x0",
+            "storageHash": "0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421",
+            "storageProof": [
+                { "key": "0x1", "value": "0x0", "proof": [] },
+                { "key": "0x0000000000000000000000000000000000000000000000000000000000000002", "value": "0x0", "proof": [] }
+            ]
+        }"#;
+        let proof: AccountProof = serde_json::from_str(json).unwrap();
+        assert_eq!(proof.address[19], 0xaa);
+        // Short slot keys, as echoed back by clients, are left-padded but
+        // keep their width
+        assert_eq!(proof.storage_proof[0].key.slot(), slot(1));
+        assert_eq!(proof.storage_proof[1].key, StorageKey::from(slot(2)));
+        assert_eq!(proof.verify(EMPTY_ROOT), Ok(()));
+        let reserialized = serde_json::to_value(&proof).unwrap();
+        assert_eq!(reserialized["storageProof"][0]["key"], "0x1");
+        assert_eq!(reserialized["storageProof"][1]["key"], format!("0x{}", hex::encode(slot(2))));
+
+        let too_long = json.replace("\"0x1\"", &format!("\"0x{}\"", "1".repeat(65)));
+        assert!(serde_json::from_str::<AccountProof>(&too_long).is_err());
+
+        let bad = json.replace("\"0x0\"", "\"0\"");
+        assert!(serde_json::from_str::<AccountProof>(&bad).is_err());
+    }
+}
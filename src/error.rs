@@ -0,0 +1,52 @@
+use crate::checkpoint::CheckpointId;
+use crate::node::Hash;
+use rlp::DecoderError;
+use std::fmt;
+use std::io;
+
+/// Errors returned by trie operations
+#[derive(Debug, Clone, PartialEq)]
+pub enum MptError {
+    /// A node referenced by the trie is not in storage
+    MissingNode(Hash),
+    /// A stored node or compact path is not validly encoded
+    InvalidEncoding(String),
+    /// A nibble path cannot be used as requested (e.g. an odd-length key)
+    InvalidPath(String),
+    /// The storage backend failed
+    StorageError(String),
+    /// A key given to a sorted-input builder is not greater than the
+    /// previous key
+    UnsortedKey(Vec<u8>),
+    /// The checkpoint was already reverted or discarded
+    UnknownCheckpoint(CheckpointId),
+}
+
+impl fmt::Display for MptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MptError::MissingNode(hash) => write!(f, "missing node 0x{}", hex::encode(hash)),
+            MptError::InvalidEncoding(msg) => write!(f, "invalid encoding: {}", msg),
+            MptError::InvalidPath(msg) => write!(f, "invalid path: {}", msg),
+            MptError::StorageError(msg) => write!(f, "storage error: {}", msg),
+            MptError::UnsortedKey(key) => {
+                write!(f, "key 0x{} is not greater than the previous key", hex::encode(key))
+            }
+            MptError::UnknownCheckpoint(id) => write!(f, "unknown checkpoint {}", id.0),
+        }
+    }
+}
+
+impl std::error::Error for MptError {}
+
+impl From<DecoderError> for MptError {
+    fn from(err: DecoderError) -> Self {
+        MptError::InvalidEncoding(err.to_string())
+    }
+}
+
+impl From<io::Error> for MptError {
+    fn from(err: io::Error) -> Self {
+        MptError::StorageError(err.to_string())
+    }
+}
@@ -0,0 +1,204 @@
+use crate::error::MptError;
+use crate::node::{Hash, keccak256};
+use crate::state::Address;
+use primitive_types::U256;
+use rlp::{Decodable, DecoderError, Rlp};
+
+/// An Ethereum block header
+///
+/// Holds the fields shared by all forks plus the London base fee. Fields
+/// added by later forks are not decoded, but the original encoding is kept so
+/// that `hash` matches the block hash for any fork.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockHeader {
+    /// Hash of the parent block
+    pub parent_hash: Hash,
+    /// Hash of the ommers (uncles) list
+    pub ommers_hash: Hash,
+    /// Address receiving the block's fees
+    pub beneficiary: Address,
+    /// Root of the state trie after the block
+    pub state_root: Hash,
+    /// Root of the transaction trie
+    pub transactions_root: Hash,
+    /// Root of the receipt trie
+    pub receipts_root: Hash,
+    /// 256-byte bloom filter over the block's logs
+    pub logs_bloom: Vec<u8>,
+    /// Proof-of-work difficulty (zero after the merge)
+    pub difficulty: U256,
+    /// Block number
+    pub number: u64,
+    /// Gas limit of the block
+    pub gas_limit: u64,
+    /// Gas used by all transactions in the block
+    pub gas_used: u64,
+    /// Unix timestamp of the block
+    pub timestamp: u64,
+    /// Arbitrary data chosen by the block producer
+    pub extra_data: Vec<u8>,
+    /// Proof-of-work mix hash (`prevRandao` after the merge)
+    pub mix_hash: Hash,
+    /// Proof-of-work nonce
+    pub nonce: [u8; 8],
+    /// Present from London onwards
+    pub base_fee_per_gas: Option<U256>,
+    /// The RLP encoding the header was decoded from
+    raw: Vec<u8>,
+}
+
+impl BlockHeader {
+    /// Decodes a header from its RLP encoding
+    pub fn decode(raw: &[u8]) -> Result<Self, MptError> {
+        // Reject trailing bytes so that the hash covers exactly this header
+        let info = Rlp::new(raw).payload_info()?;
+        if info.header_len + info.value_len != raw.len() {
+            return Err(DecoderError::RlpInconsistentLengthAndData.into());
+        }
+        Ok(rlp::decode(raw)?)
+    }
+
+    /// Returns the block hash: Keccak-256 of the header's RLP encoding
+    pub fn hash(&self) -> Hash {
+        keccak256(&self.raw)
+    }
+
+    /// Returns the RLP encoding of the header
+    pub fn encoded(&self) -> &[u8] {
+        &self.raw
+    }
+}
+
+impl Decodable for BlockHeader {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if !rlp.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        let item_count = rlp.item_count()?;
+        if item_count < 15 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        let logs_bloom: Vec<u8> = rlp.val_at(6)?;
+        if logs_bloom.len() != 256 {
+            return Err(DecoderError::Custom("Invalid logs bloom length"));
+        }
+        let base_fee_per_gas = if item_count > 15 { Some(rlp.val_at(15)?) } else { None };
+
+        Ok(Self {
+            parent_hash: fixed_at(rlp, 0)?,
+            ommers_hash: fixed_at(rlp, 1)?,
+            beneficiary: fixed_at(rlp, 2)?,
+            state_root: fixed_at(rlp, 3)?,
+            transactions_root: fixed_at(rlp, 4)?,
+            receipts_root: fixed_at(rlp, 5)?,
+            logs_bloom,
+            difficulty: rlp.val_at(7)?,
+            number: rlp.val_at(8)?,
+            gas_limit: rlp.val_at(9)?,
+            gas_used: rlp.val_at(10)?,
+            timestamp: rlp.val_at(11)?,
+            extra_data: rlp.val_at(12)?,
+            mix_hash: fixed_at(rlp, 13)?,
+            nonce: fixed_at(rlp, 14)?,
+            base_fee_per_gas,
+            raw: rlp.as_raw().to_vec(),
+        })
+    }
+}
+
+/// Helper: decodes a fixed-length byte string at list position `index`
+pub(crate) fn fixed_at<const N: usize>(rlp: &Rlp, index: usize) -> Result<[u8; N], DecoderError> {
+    rlp.at(index)?.decoder().decode_value(|bytes| {
+        bytes.try_into().map_err(|_| DecoderError::Custom("Invalid fixed-length field"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::EMPTY_ROOT;
+    use rlp::RlpStream;
+
+    /// Encodes a header with the given receipts root, optionally with a base fee
+    fn sample_header(receipts_root: Hash, base_fee: Option<u64>) -> Vec<u8> {
+        let mut stream = RlpStream::new_list(if base_fee.is_some() { 16 } else { 15 });
+        stream
+            .append(&[0x01; 32].as_slice())
+            .append(&[0x02; 32].as_slice())
+            .append(&[0x03; 20].as_slice())
+            .append(&EMPTY_ROOT.as_slice())
+            .append(&EMPTY_ROOT.as_slice())
+            .append(&receipts_root.as_slice())
+            .append(&vec![0u8; 256])
+            .append(&U256::zero())
+            .append(&17_000_000u64)
+            .append(&30_000_000u64)
+            .append(&21_000u64)
+            .append(&1_700_000_000u64)
+            .append(&b"extra".to_vec())
+            .append(&[0x04; 32].as_slice())
+            .append(&[0u8; 8].as_slice());
+        if let Some(base_fee) = base_fee {
+            stream.append(&base_fee);
+        }
+        stream.out().to_vec()
+    }
+
+    #[test]
+    fn test_decode_header() {
+        let raw = sample_header([0xAA; 32], Some(7));
+        let header = BlockHeader::decode(&raw).unwrap();
+        assert_eq!(header.receipts_root, [0xAA; 32]);
+        assert_eq!(header.beneficiary, [0x03; 20]);
+        assert_eq!(header.number, 17_000_000);
+        assert_eq!(header.extra_data, b"extra".to_vec());
+        assert_eq!(header.base_fee_per_gas, Some(U256::from(7)));
+        assert_eq!(header.hash(), keccak256(&raw));
+        assert_eq!(header.encoded(), raw.as_slice());
+
+        let legacy = BlockHeader::decode(&sample_header([0xAA; 32], None)).unwrap();
+        assert_eq!(legacy.base_fee_per_gas, None);
+        assert_ne!(legacy.hash(), header.hash());
+    }
+
+    #[test]
+    fn test_mainnet_genesis_header() {
+        let hash = |s: &str| -> Hash { hex::decode(s).unwrap().try_into().unwrap() };
+        let mut stream = RlpStream::new_list(15);
+        stream
+            .append(&[0u8; 32].as_slice())
+            .append(&hash("1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347").as_slice())
+            .append(&[0u8; 20].as_slice())
+            .append(&hash("d7f8974fb5ac78d9ac099b9ad5018bedc2ce0a72dad1827a1709da30580f0544").as_slice())
+            .append(&EMPTY_ROOT.as_slice())
+            .append(&EMPTY_ROOT.as_slice())
+            .append(&vec![0u8; 256])
+            .append(&U256::from(0x4_0000_0000u64))
+            .append(&0u64)
+            .append(&5000u64)
+            .append(&0u64)
+            .append(&0u64)
+            .append(&hex::decode("11bbe8db4e347b4e8c937c1c8370e4b5ed33adb3db69cbdb7a38e1e50b1b82fa").unwrap())
+            .append(&[0u8; 32].as_slice())
+            .append(&[0, 0, 0, 0, 0, 0, 0, 0x42u8].as_slice());
+
+        let header = BlockHeader::decode(&stream.out()).unwrap();
+        assert_eq!(header.hash(), hash("d4e56740f876aef8c010b86a40d5f56745a118d0906a34e69aec8c0db1cb8fa3"));
+        assert_eq!(header.number, 0);
+        assert_eq!(header.gas_limit, 5000);
+        assert_eq!(header.receipts_root, EMPTY_ROOT);
+        assert_eq!(header.base_fee_per_gas, None);
+    }
+
+    #[test]
+    fn test_invalid_header_rejected() {
+        let raw = sample_header([0xAA; 32], None);
+        assert!(BlockHeader::decode(&raw[..raw.len() - 1]).is_err());
+        assert!(BlockHeader::decode(&[raw.as_slice(), &[0x00]].concat()).is_err());
+
+        let mut stream = RlpStream::new_list(3);
+        stream.append(&1u64).append(&2u64).append(&3u64);
+        assert!(matches!(BlockHeader::decode(&stream.out()), Err(MptError::InvalidEncoding(_))));
+    }
+}
@@ -0,0 +1,651 @@
+use crate::db::NodeDb;
+use crate::error::MptError;
+use crate::nibbles::{bytes_to_nibbles, compact_decode, nibbles_to_bytes};
+use crate::node::{Link, TrieNode};
+use crate::trie::MerklePatriciaTrie;
+use std::cmp::Ordering;
+use std::ops::{Bound, RangeBounds};
+
+/// A key/value pair as yielded by the iterators
+type Entry = (Vec<u8>, Vec<u8>);
+
+/// Iterator over the key/value pairs of a trie in lexicographic key order
+///
+/// Walks the trie depth-first, visiting a branch's own value before its
+/// children 0..15, and rebuilds each key from the nibbles along the path.
+/// Nodes are loaded from storage lazily as the walk reaches them; a node that
+/// cannot be loaded is reported as an error and its subtrie skipped.
+pub struct TrieIter<'a, D: NodeDb> {
+    trie: &'a MerklePatriciaTrie<D>,
+    /// Nodes still to visit, with the nibble path leading to each
+    stack: Vec<(Vec<u8>, Link)>,
+}
+
+impl<'a, D: NodeDb> TrieIter<'a, D> {
+    /// Creates an iterator over the subtrie behind `child`, whose path from
+    /// the root is `prefix`
+    pub(crate) fn new(trie: &'a MerklePatriciaTrie<D>, prefix: Vec<u8>, child: Link) -> Self {
+        Self { trie, stack: vec![(prefix, child)] }
+    }
+
+    /// Helper: visits the node behind `child`, returning its entry if it
+    /// holds a value
+    fn visit(&mut self, path: Vec<u8>, child: &Link) -> Result<Option<Entry>, MptError> {
+        match self.trie.resolve(child)? {
+            TrieNode::Empty => Ok(None),
+
+            TrieNode::Leaf(encoded_path, value) => {
+                let (leaf_path, _) = compact_decode(&encoded_path)?;
+                let key = [path, leaf_path].concat();
+                Ok(Some((nibbles_to_bytes(&key)?, value)))
+            }
+
+            TrieNode::Extension(encoded_path, ext_child) => {
+                let (ext_path, _) = compact_decode(&encoded_path)?;
+                self.stack.push(([path, ext_path].concat(), ext_child));
+                Ok(None)
+            }
+
+            TrieNode::Branch(children, branch_value) => {
+                // Push in reverse so that child 0 is visited first
+                for (idx, branch_child) in children.into_iter().enumerate().rev() {
+                    if let Some(branch_child) = branch_child {
+                        let mut child_path = path.clone();
+                        child_path.push(idx as u8);
+                        self.stack.push((child_path, branch_child));
+                    }
+                }
+                match branch_value {
+                    Some(value) => Ok(Some((nibbles_to_bytes(&path)?, value))),
+                    None => Ok(None),
+                }
+            }
+        }
+    }
+}
+
+impl<D: NodeDb> Iterator for TrieIter<'_, D> {
+    type Item = Result<Entry, MptError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((path, child)) = self.stack.pop() {
+            if let Some(item) = self.visit(path, &child).transpose() {
+                return Some(item);
+            }
+        }
+        None
+    }
+}
+
+/// Where a cursor that is on no entry stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edge {
+    /// Fresh, or after a failed step
+    Unset,
+    /// Stepped back past the first key
+    Start,
+    /// Stepped past the last key
+    End,
+}
+
+/// A node on the path to the cursor's entry, with the slot the path takes
+///
+/// Slot 0 is the node's own value: a leaf's, or a branch's. Slot `i + 1` is
+/// branch child `i`; an extension's only child is slot 1.
+struct Frame {
+    node: TrieNode,
+    /// Length of the nibble path leading to the node
+    depth: usize,
+    slot: usize,
+}
+
+/// A position in a trie's ordered key space
+///
+/// A fresh cursor is unpositioned: `next` moves it to the first key and `prev`
+/// to the last. `seek` jumps to the first key greater than or equal to a
+/// target. Stepping past either end leaves the cursor there: after the last
+/// key `next` keeps returning `None` until `prev` or `seek` moves the cursor
+/// back, and likewise for `prev` before the first key. A failed step leaves
+/// the cursor unpositioned, as if fresh.
+///
+/// The cursor holds the nodes on the path from the root to its entry, so a
+/// step only loads the nodes between one entry and the next.
+pub struct TrieCursor<'a, D: NodeDb> {
+    trie: &'a MerklePatriciaTrie<D>,
+    /// Nodes from the root down to the current entry
+    stack: Vec<Frame>,
+    /// Nibble path to the current entry
+    path: Vec<u8>,
+    /// The entry the cursor is on
+    current: Option<Entry>,
+    /// Where the cursor stands while it is on no entry
+    edge: Edge,
+}
+
+impl<'a, D: NodeDb> TrieCursor<'a, D> {
+    pub(crate) fn new(trie: &'a MerklePatriciaTrie<D>) -> Self {
+        Self { trie, stack: Vec::new(), path: Vec::new(), current: None, edge: Edge::Unset }
+    }
+
+    /// Returns the entry the cursor is on, if any
+    pub fn current(&self) -> Option<Entry> {
+        self.current.clone()
+    }
+
+    /// Moves to the first key greater than or equal to `key` and returns it
+    ///
+    /// Moves past the last key if every key is smaller.
+    pub fn seek(&mut self, key: &[u8]) -> Result<Option<Entry>, MptError> {
+        let target = bytes_to_nibbles(key);
+        self.seek_nibbles(&target, false)
+    }
+
+    /// Moves to the previous key and returns it
+    ///
+    /// From a fresh cursor, or one past the last key, this moves to the last
+    /// key.
+    pub fn prev(&mut self) -> Result<Option<Entry>, MptError> {
+        let found = match (&self.current, self.edge) {
+            (Some(_), _) => self.step(false),
+            (None, Edge::Start) => return Ok(None),
+            (None, _) => self.descend(self.trie.root_ref(), 0, true),
+        };
+        self.settle(found, Edge::Start)
+    }
+
+    /// Helper: moves to the first key after `target` (or equal to it,
+    /// unless `strict`)
+    fn seek_nibbles(&mut self, target: &[u8], strict: bool) -> Result<Option<Entry>, MptError> {
+        self.stack.clear();
+        self.path.clear();
+        let found = self.seek_from_root(target, strict);
+        self.settle(found, Edge::End)
+    }
+
+    /// Helper: records the outcome of a move, leaving the cursor at `edge` if
+    /// it ran out of keys
+    fn settle(&mut self, found: Result<Option<Vec<u8>>, MptError>, edge: Edge) -> Result<Option<Entry>, MptError> {
+        let entry = found.and_then(|value| match value {
+            Some(value) => Ok(Some((nibbles_to_bytes(&self.path)?, value))),
+            None => Ok(None),
+        });
+        self.current = entry.clone().ok().flatten();
+        if self.current.is_none() {
+            self.stack.clear();
+            self.path.clear();
+            self.edge = if entry.is_ok() { edge } else { Edge::Unset };
+        }
+        entry
+    }
+
+    /// Helper: descends from the root towards `target`, stopping at the
+    /// first key after it (or at it, unless `strict`)
+    ///
+    /// Returns the value of the key found.
+    fn seek_from_root(&mut self, mut target: &[u8], strict: bool) -> Result<Option<Vec<u8>>, MptError> {
+        let mut child = self.trie.root_ref();
+        loop {
+            let depth = self.path.len();
+            let node = self.trie.resolve(&child)?;
+            match &node {
+                TrieNode::Empty => return Ok(None),
+
+                TrieNode::Leaf(encoded_path, value) => {
+                    let (leaf_path, _) = compact_decode(encoded_path)?;
+                    let qualifies = match leaf_path.as_slice().cmp(target) {
+                        Ordering::Greater => true,
+                        Ordering::Equal => !strict,
+                        Ordering::Less => false,
+                    };
+                    let value = qualifies.then(|| value.clone());
+                    self.path.extend(leaf_path);
+                    self.stack.push(Frame { node, depth, slot: 0 });
+                    return if value.is_some() { Ok(value) } else { self.step(true) };
+                }
+
+                TrieNode::Extension(encoded_path, ext_child) => {
+                    let (ext_path, _) = compact_decode(encoded_path)?;
+                    let ext_child = ext_child.clone();
+                    self.path.extend(&ext_path);
+                    self.stack.push(Frame { node, depth, slot: 1 });
+                    if let Some(rest) = target.strip_prefix(ext_path.as_slice()) {
+                        target = rest;
+                        child = ext_child;
+                        continue;
+                    }
+                    // The paths diverge, or the target ends within the
+                    // extension: the whole subtrie is after or before it
+                    if ext_path.as_slice() > target
+                        && let Some(value) = self.descend(ext_child, self.path.len(), false)?
+                    {
+                        return Ok(Some(value));
+                    }
+                    return self.step(true);
+                }
+
+                TrieNode::Branch(children, value) => {
+                    let Some((&idx, rest)) = target.split_first() else {
+                        let value = value.clone().filter(|_| !strict);
+                        self.stack.push(Frame { node, depth, slot: 0 });
+                        return if value.is_some() { Ok(value) } else { self.step(true) };
+                    };
+                    let branch_child = children[idx as usize].clone();
+                    self.path.push(idx);
+                    self.stack.push(Frame { node, depth, slot: idx as usize + 1 });
+                    match branch_child {
+                        Some(branch_child) => {
+                            target = rest;
+                            child = branch_child;
+                        }
+                        // Later slots are entirely after the target
+                        None => return self.step(true),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Helper: moves from the node at the top of the stack to the next entry
+    /// in the given direction, popping the nodes that hold no further entry
+    ///
+    /// Returns the value of the entry reached, or `None` past the last (or
+    /// before the first) key.
+    fn step(&mut self, forward: bool) -> Result<Option<Vec<u8>>, MptError> {
+        while let Some(frame) = self.stack.last_mut() {
+            let TrieNode::Branch(children, value) = &frame.node else {
+                // Leaves and extensions hold a single entry or child
+                self.stack.pop();
+                continue;
+            };
+            let mut slots = (1..=16).filter(|&slot| children[slot - 1].is_some());
+            let next = if forward {
+                slots.find(|&slot| slot > frame.slot)
+            } else {
+                // A branch's own value comes before its children
+                slots.rfind(|&slot| slot < frame.slot).or((frame.slot > 0 && value.is_some()).then_some(0))
+            };
+
+            let depth = frame.depth;
+            self.path.truncate(depth);
+            match next {
+                None => {
+                    self.stack.pop();
+                }
+                Some(0) => {
+                    let value = value.clone();
+                    frame.slot = 0;
+                    return Ok(value);
+                }
+                Some(slot) => {
+                    let child = children[slot - 1].clone().expect("slot holds a child");
+                    frame.slot = slot;
+                    self.path.push(slot as u8 - 1);
+                    if let Some(value) = self.descend(child, depth + 1, !forward)? {
+                        return Ok(Some(value));
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Helper: descends from `child`, whose path is `depth` nibbles long, to
+    /// the first entry below it (or the last, if `last`)
+    ///
+    /// Returns the value of that entry, or `None` if the subtrie is empty.
+    fn descend(&mut self, mut child: Link, mut depth: usize, last: bool) -> Result<Option<Vec<u8>>, MptError> {
+        let height = self.stack.len();
+        loop {
+            self.path.truncate(depth);
+            let node = self.trie.resolve(&child)?;
+            let (slot, next, value) = match &node {
+                TrieNode::Empty => (None, None, None),
+                TrieNode::Leaf(encoded_path, value) => {
+                    self.path.extend(compact_decode(encoded_path)?.0);
+                    (Some(0), None, Some(value.clone()))
+                }
+                TrieNode::Extension(encoded_path, ext_child) => {
+                    self.path.extend(compact_decode(encoded_path)?.0);
+                    (Some(1), Some(ext_child.clone()), None)
+                }
+                TrieNode::Branch(children, value) => {
+                    let mut slots = (1..=16).filter(|&slot| children[slot - 1].is_some());
+                    let child_slot = if last { slots.next_back() } else { slots.next() };
+                    match (child_slot, value) {
+                        // Going forwards, the value comes before the children
+                        (Some(_), Some(value)) if !last => (Some(0), None, Some(value.clone())),
+                        (Some(slot), _) => {
+                            self.path.push(slot as u8 - 1);
+                            (Some(slot), children[slot - 1].clone(), None)
+                        }
+                        (None, Some(value)) => (Some(0), None, Some(value.clone())),
+                        (None, None) => (None, None, None),
+                    }
+                }
+            };
+
+            let Some(slot) = slot else {
+                self.stack.truncate(height);
+                return Ok(None);
+            };
+            self.stack.push(Frame { node, depth, slot });
+            match next {
+                Some(next) => {
+                    child = next;
+                    depth = self.path.len();
+                }
+                None => return Ok(value),
+            }
+        }
+    }
+}
+
+impl<D: NodeDb> Iterator for TrieCursor<'_, D> {
+    type Item = Result<Entry, MptError>;
+
+    /// Moves to the next key and returns it
+    ///
+    /// From a fresh cursor, or one before the first key, this moves to the
+    /// first key.
+    fn next(&mut self) -> Option<Self::Item> {
+        let found = match (&self.current, self.edge) {
+            (Some(_), _) => self.step(true),
+            (None, Edge::End) => return None,
+            (None, _) => self.descend(self.trie.root_ref(), 0, false),
+        };
+        self.settle(found, Edge::End).transpose()
+    }
+}
+
+/// Iterator over the keys of a trie that fall within a range
+pub struct TrieRange<'a, D: NodeDb> {
+    cursor: TrieCursor<'a, D>,
+    /// The first entry, already located by the initial seek
+    first: Option<Result<Entry, MptError>>,
+    end: Bound<Vec<u8>>,
+    done: bool,
+}
+
+impl<'a, D: NodeDb> TrieRange<'a, D> {
+    pub(crate) fn new<K: AsRef<[u8]>, R: RangeBounds<K>>(trie: &'a MerklePatriciaTrie<D>, range: R) -> Self {
+        let mut cursor = TrieCursor::new(trie);
+        let first = match range.start_bound() {
+            Bound::Included(start) => cursor.seek(start.as_ref()).transpose(),
+            Bound::Excluded(start) => {
+                let target = bytes_to_nibbles(start.as_ref());
+                cursor.seek_nibbles(&target, true).transpose()
+            }
+            Bound::Unbounded => cursor.next(),
+        };
+        let end = match range.end_bound() {
+            Bound::Included(end) => Bound::Included(end.as_ref().to_vec()),
+            Bound::Excluded(end) => Bound::Excluded(end.as_ref().to_vec()),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        Self { cursor, first, end, done: false }
+    }
+}
+
+impl<D: NodeDb> Iterator for TrieRange<'_, D> {
+    type Item = Result<Entry, MptError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let item = match self.first.take() {
+            Some(item) => Some(item),
+            None => self.cursor.next(),
+        };
+        let in_range = match (&item, &self.end) {
+            (None, _) => false,
+            // Report the error, then stop: the cursor has lost its position
+            (Some(Err(_)), _) => {
+                self.done = true;
+                return item;
+            }
+            (Some(Ok(_)), Bound::Unbounded) => true,
+            (Some(Ok((key, _))), Bound::Included(end)) => key <= end,
+            (Some(Ok((key, _))), Bound::Excluded(end)) => key < end,
+        };
+        if in_range {
+            item
+        } else {
+            self.done = true;
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDb;
+    use crate::node::{Hash, keccak256};
+    use crate::test_utils::XorShift;
+    use std::cell::Cell;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    #[test]
+    fn test_iter_empty() {
+        let trie = MerklePatriciaTrie::new();
+        assert!(trie.iter().next().is_none());
+    }
+
+    #[test]
+    fn test_iter_sorted() {
+        let mut trie = MerklePatriciaTrie::new();
+        let mut expected = BTreeMap::new();
+        for (key, value) in [
+            (&b"horse"[..], &b"stallion"[..]),
+            (b"do", b"verb"),
+            (b"doge", b"coin"),
+            (b"dog", b"puppy"),
+            (b"", b"root"),
+            (b"\x00\xff", b"low"),
+        ] {
+            trie.insert(key, value.to_vec()).unwrap();
+            expected.insert(key.to_vec(), value.to_vec());
+        }
+
+        let items: Vec<_> = trie.iter().collect::<Result<_, _>>().unwrap();
+        assert_eq!(items, expected.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_iter_after_deletes() {
+        let mut trie = MerklePatriciaTrie::new();
+        for i in 0..=255u8 {
+            trie.insert(&[i, i], vec![i; 33]).unwrap();
+        }
+        for i in (0..=255u8).step_by(2) {
+            trie.delete(&[i, i]).unwrap();
+        }
+
+        let keys: Vec<_> = trie.iter().map(|item| item.unwrap().0).collect();
+        let expected: Vec<_> = (0..=255u8).skip(1).step_by(2).map(|i| vec![i, i]).collect();
+        assert_eq!(keys, expected);
+    }
+
+    fn sample() -> (MerklePatriciaTrie, BTreeMap<Vec<u8>, Vec<u8>>) {
+        let mut trie = MerklePatriciaTrie::new();
+        let mut expected = BTreeMap::new();
+        for key in [
+            &b"do"[..], b"dog", b"doge", b"dogs", b"dot", b"horse", b"hose", b"", b"\x10", b"\x10\x01", b"\xff",
+        ] {
+            let value = [key, b"!"].concat();
+            trie.insert(key, value.clone()).unwrap();
+            expected.insert(key.to_vec(), value);
+        }
+        (trie, expected)
+    }
+
+    #[test]
+    fn test_iter_prefix() {
+        let (trie, expected) = sample();
+        for prefix in [&b""[..], b"d", b"do", b"dog", b"doge", b"ho", b"x", b"\x10", b"dogex"] {
+            let got: Vec<_> = trie.iter_prefix(prefix).unwrap().collect::<Result<_, _>>().unwrap();
+            let want: Vec<_> = expected
+                .iter()
+                .filter(|(k, _)| k.starts_with(prefix))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+            assert_eq!(got, want, "prefix {:?}", prefix);
+        }
+    }
+
+    #[test]
+    fn test_range() {
+        let (trie, expected) = sample();
+        let bounds: [&[u8]; 8] = [b"", b"a", b"do", b"dog", b"doga", b"dz", b"hose", b"\xff\xff"];
+        for start in bounds {
+            for end in bounds {
+                let within = |excl: bool| -> Vec<_> {
+                    expected
+                        .iter()
+                        .filter(|(k, _)| k.as_slice() >= start && (k.as_slice() < end || (!excl && k.as_slice() == end)))
+                        .map(|(k, v)| (k.clone(), v.clone()))
+                        .collect()
+                };
+                let got: Vec<_> = trie.range(start..end).collect::<Result<_, _>>().unwrap();
+                assert_eq!(got, within(true), "range {:?}..{:?}", start, end);
+                let got: Vec<_> = trie.range(start..=end).collect::<Result<_, _>>().unwrap();
+                assert_eq!(got, within(false), "range {:?}..={:?}", start, end);
+            }
+        }
+        assert_eq!(trie.range::<&[u8], _>(..).count(), expected.len());
+    }
+
+    #[test]
+    fn test_cursor_seek_next_prev() {
+        let (trie, expected) = sample();
+        let keys: Vec<_> = expected.keys().cloned().collect();
+
+        let mut cursor = trie.cursor();
+        assert_eq!(cursor.current(), None);
+        let forward: Vec<_> = cursor.by_ref().map(|item| item.unwrap().0).collect();
+        assert_eq!(forward, keys);
+
+        let mut cursor = trie.cursor();
+        let mut backward = Vec::new();
+        while let Some((k, _)) = cursor.prev().unwrap() {
+            backward.push(k);
+        }
+        backward.reverse();
+        assert_eq!(backward, keys);
+
+        let mut cursor = trie.cursor();
+        assert_eq!(cursor.seek(b"doga").unwrap().map(|(k, _)| k), Some(b"doge".to_vec()));
+        assert_eq!(cursor.next().transpose().unwrap().map(|(k, _)| k), Some(b"dogs".to_vec()));
+        assert_eq!(cursor.prev().unwrap().map(|(k, _)| k), Some(b"doge".to_vec()));
+        assert_eq!(cursor.prev().unwrap().map(|(k, _)| k), Some(b"dog".to_vec()));
+        assert_eq!(cursor.seek(b"hose").unwrap().map(|(k, _)| k), Some(b"hose".to_vec()));
+        assert_eq!(cursor.seek(b"\xff\x00").unwrap(), None);
+        assert_eq!(cursor.current(), None);
+    }
+
+    #[test]
+    fn test_cursor_stays_past_either_end() {
+        let (trie, expected) = sample();
+        let first = expected.keys().next().cloned();
+        let last = expected.keys().next_back().cloned();
+
+        let mut cursor = trie.cursor();
+        assert_eq!(cursor.by_ref().count(), expected.len());
+        assert!(cursor.next().is_none());
+        assert!(cursor.next().is_none());
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.prev().unwrap().map(|(k, _)| k), last);
+
+        while cursor.prev().unwrap().is_some() {}
+        assert_eq!(cursor.prev().unwrap(), None);
+        assert_eq!(cursor.next().transpose().unwrap().map(|(k, _)| k), first);
+    }
+
+    #[test]
+    fn test_cursor_matches_sorted_map() {
+        let mut rng = XorShift(0xC0_FFEE);
+        for _ in 0..50 {
+            let mut trie = MerklePatriciaTrie::new();
+            let mut keys = BTreeSet::new();
+            for _ in 0..30 {
+                // Short keys over a small alphabet make keys that prefix others
+                let len = (rng.next() % 4) as usize;
+                let key: Vec<u8> = (0..len).map(|_| [0x00, 0x01, 0x10, 0xAB][(rng.next() % 4) as usize]).collect();
+                trie.insert(&key, vec![len as u8; 1 + (rng.next() % 40) as usize]).unwrap();
+                keys.insert(key);
+            }
+            if rng.next().is_multiple_of(2) {
+                trie.commit().unwrap();
+            }
+            let keys: Vec<Vec<u8>> = keys.into_iter().collect();
+
+            let mut cursor = trie.cursor();
+            let target: Vec<u8> = (0..rng.next() % 4).map(|_| [0x00, 0x01, 0x02, 0xAB][(rng.next() % 4) as usize]).collect();
+            // Position in `keys`: -1 before the first key, `keys.len()` past the last
+            let mut pos = keys.partition_point(|key| *key < target) as isize;
+            assert_eq!(cursor.seek(&target).unwrap().map(|(k, _)| k), keys.get(pos as usize).cloned());
+
+            for _ in 0..40 {
+                let got = if rng.next().is_multiple_of(2) {
+                    pos = (pos + 1).min(keys.len() as isize);
+                    cursor.next().transpose().unwrap()
+                } else {
+                    pos = (pos - 1).max(-1);
+                    cursor.prev().unwrap()
+                };
+                let want = usize::try_from(pos).ok().and_then(|pos| keys.get(pos)).cloned();
+                assert_eq!(got.map(|(k, _)| k), want);
+                assert_eq!(cursor.current().map(|(k, _)| k), want);
+            }
+        }
+    }
+
+    /// A store that counts node reads
+    struct CountingReads {
+        inner: MemoryDb,
+        reads: Cell<usize>,
+    }
+
+    impl NodeDb for CountingReads {
+        fn get(&self, hash: &Hash) -> Result<Option<Vec<u8>>, MptError> {
+            self.reads.set(self.reads.get() + 1);
+            self.inner.get(hash)
+        }
+
+        fn put(&mut self, hash: Hash, encoded: Vec<u8>) -> Result<(), MptError> {
+            self.inner.put(hash, encoded)
+        }
+
+        fn remove(&mut self, hash: &Hash) -> Result<(), MptError> {
+            self.inner.remove(hash)
+        }
+
+        fn hashes(&self) -> Result<Vec<Hash>, MptError> {
+            self.inner.hashes()
+        }
+    }
+
+    #[test]
+    fn test_cursor_loads_each_node_once() {
+        let mut trie = MerklePatriciaTrie::new();
+        for i in 0..2000u32 {
+            trie.insert(&keccak256(&i.to_be_bytes()), vec![0xAB; 40]).unwrap();
+        }
+        let root = trie.commit().unwrap();
+        let stored = trie.db().len();
+        let db = CountingReads { inner: trie.into_db(), reads: Cell::new(0) };
+        let trie = MerklePatriciaTrie::with_db(db, root);
+
+        // A full walk in either direction reads every node exactly once
+        let mut cursor = trie.cursor();
+        assert_eq!(cursor.by_ref().count(), 2000);
+        assert_eq!(trie.db().reads.get(), stored);
+        let mut count = 0;
+        while cursor.prev().unwrap().is_some() {
+            count += 1;
+        }
+        assert_eq!(count, 2000);
+        assert_eq!(trie.db().reads.get(), 2 * stored);
+    }
+}
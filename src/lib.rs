@@ -11,25 +11,64 @@
 //! let mut trie = MerklePatriciaTrie::new();
 //! 
 //! // Insert key-value pairs
-//! trie.insert(b"dog", b"puppy".to_vec());
-//! trie.insert(b"doge", b"coin".to_vec());
+//! trie.insert(b"dog", b"puppy".to_vec())?;
+//! trie.insert(b"doge", b"coin".to_vec())?;
 //! 
 //! // Retrieve values
-//! assert_eq!(trie.get(b"dog"), Some(b"puppy".to_vec()));
+//! assert_eq!(trie.get(b"dog")?, Some(b"puppy".to_vec()));
 //! 
 //! // Get root hash
 //! let root = trie.root_hash();
 //! println!("Root hash: {:?}", hex::encode(root));
 //! 
 //! // Delete keys
-//! trie.delete(b"dog");
-//! assert_eq!(trie.get(b"dog"), None);
+//! trie.delete(b"dog")?;
+//! assert_eq!(trie.get(b"dog")?, None);
+//! # Ok::<(), mpt::MptError>(())
 //! ```
 
+pub mod account;
+pub mod batch;
+pub mod checkpoint;
+pub mod db;
+pub mod diff;
+pub mod eip1186;
+pub mod error;
+pub mod header;
+pub mod iter;
+pub mod multiproof;
 pub mod nibbles;
 pub mod node;
+pub mod ordered;
+pub mod proof;
+pub mod receipt;
+pub mod secure;
+pub mod stack;
+pub mod state;
 pub mod trie;
+pub mod version;
+
+#[cfg(test)]
+mod test_utils;
 
 pub use trie::MerklePatriciaTrie;
-pub use node::{Node, Hash};
+pub use batch::Op;
+pub use checkpoint::CheckpointId;
+pub use diff::{Change, TrieDiff};
+pub use iter::{TrieIter, TrieCursor, TrieRange};
+pub use version::{Retention, TrieView};
+pub use db::{NodeDb, MemoryDb, FileDb};
+pub use error::MptError;
+pub use node::{Node, Hash, ChildRef, EMPTY_ROOT};
+pub use proof::{MerkleProof, ProofError, verify_proof};
+pub use multiproof::{MultiProof, verify_multiproof};
+pub use secure::{SecureTrie, SecureTrieIter};
+pub use stack::StackTrie;
+pub use account::{Account, EMPTY_CODE_HASH};
+pub use state::{StateTrie, Address};
+pub use eip1186::{AccountProof, StorageProof, StorageKey, AccountProofError};
+pub use ordered::{OrderedTrie, TransactionTrie, ReceiptTrie, ordered_trie_root};
+pub use header::BlockHeader;
+pub use receipt::{Log, LogFilter, Receipt, ReceiptOutcome, ReceiptProof, InclusionError};
+pub use primitive_types::U256;
 
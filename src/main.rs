@@ -1,6 +1,6 @@
-use mpt::MerklePatriciaTrie;
+use mpt::{MerklePatriciaTrie, MptError};
 
-fn main() {
+fn main() -> Result<(), MptError> {
     println!("=== Merkle Patricia Trie Demo ===\n");
 
     // Create a new trie
@@ -10,51 +10,52 @@ fn main() {
 
     // Insert some values
     println!("2. Inserting key-value pairs:");
-    trie.insert(b"do", b"verb".to_vec());
+    trie.insert(b"do", b"verb".to_vec())?;
     println!("   Inserted: 'do' -> 'verb'");
     println!("   Root hash: {}", hex::encode(trie.root_hash()));
     
-    trie.insert(b"dog", b"puppy".to_vec());
+    trie.insert(b"dog", b"puppy".to_vec())?;
     println!("   Inserted: 'dog' -> 'puppy'");
     println!("   Root hash: {}", hex::encode(trie.root_hash()));
     
-    trie.insert(b"doge", b"coin".to_vec());
+    trie.insert(b"doge", b"coin".to_vec())?;
     println!("   Inserted: 'doge' -> 'coin'");
     println!("   Root hash: {}", hex::encode(trie.root_hash()));
     
-    trie.insert(b"horse", b"stallion".to_vec());
+    trie.insert(b"horse", b"stallion".to_vec())?;
     println!("   Inserted: 'horse' -> 'stallion'");
     println!("   Root hash: {}\n", hex::encode(trie.root_hash()));
 
     // Retrieve values
     println!("3. Retrieving values:");
-    println!("   'do' -> {:?}", String::from_utf8_lossy(&trie.get(b"do").unwrap()));
-    println!("   'dog' -> {:?}", String::from_utf8_lossy(&trie.get(b"dog").unwrap()));
-    println!("   'doge' -> {:?}", String::from_utf8_lossy(&trie.get(b"doge").unwrap()));
-    println!("   'horse' -> {:?}", String::from_utf8_lossy(&trie.get(b"horse").unwrap()));
-    println!("   'cat' -> {:?}\n", trie.get(b"cat"));
+    println!("   'do' -> {:?}", String::from_utf8_lossy(&trie.get(b"do")?.unwrap()));
+    println!("   'dog' -> {:?}", String::from_utf8_lossy(&trie.get(b"dog")?.unwrap()));
+    println!("   'doge' -> {:?}", String::from_utf8_lossy(&trie.get(b"doge")?.unwrap()));
+    println!("   'horse' -> {:?}", String::from_utf8_lossy(&trie.get(b"horse")?.unwrap()));
+    println!("   'cat' -> {:?}\n", trie.get(b"cat")?);
 
     // Update a value
     println!("4. Updating a value:");
-    trie.insert(b"dog", b"animal".to_vec());
+    trie.insert(b"dog", b"animal".to_vec())?;
     println!("   Updated: 'dog' -> 'animal'");
-    println!("   'dog' -> {:?}", String::from_utf8_lossy(&trie.get(b"dog").unwrap()));
+    println!("   'dog' -> {:?}", String::from_utf8_lossy(&trie.get(b"dog")?.unwrap()));
     println!("   Root hash: {}\n", hex::encode(trie.root_hash()));
 
     // Delete a value
     println!("5. Deleting a value:");
-    trie.delete(b"dog");
+    trie.delete(b"dog")?;
     println!("   Deleted: 'dog'");
-    println!("   'dog' -> {:?}", trie.get(b"dog"));
+    println!("   'dog' -> {:?}", trie.get(b"dog")?);
     println!("   Root hash: {}\n", hex::encode(trie.root_hash()));
 
     // Verify other values are still present
     println!("6. Verifying remaining values:");
-    println!("   'do' -> {:?}", String::from_utf8_lossy(&trie.get(b"do").unwrap()));
-    println!("   'doge' -> {:?}", String::from_utf8_lossy(&trie.get(b"doge").unwrap()));
-    println!("   'horse' -> {:?}", String::from_utf8_lossy(&trie.get(b"horse").unwrap()));
+    println!("   'do' -> {:?}", String::from_utf8_lossy(&trie.get(b"do")?.unwrap()));
+    println!("   'doge' -> {:?}", String::from_utf8_lossy(&trie.get(b"doge")?.unwrap()));
+    println!("   'horse' -> {:?}", String::from_utf8_lossy(&trie.get(b"horse")?.unwrap()));
 
     println!("\n=== Demo Complete ===");
+    Ok(())
 }
 
 #[cfg(test)]
@@ -66,63 +67,63 @@ mod tests {
         let mut trie: MerklePatriciaTrie = MerklePatriciaTrie::new();
         
         // Test insert and get
-        trie.insert(b"key1", b"value1".to_vec());
-        assert_eq!(trie.get(b"key1"), Some(b"value1".to_vec()));
+        trie.insert(b"key1", b"value1".to_vec()).unwrap();
+        assert_eq!(trie.get(b"key1").unwrap(), Some(b"value1".to_vec()));
         
         // Test non-existent key
-        assert_eq!(trie.get(b"key2"), None);
+        assert_eq!(trie.get(b"key2").unwrap(), None);
     }
 
     #[test]
     fn test_update_value() {
         let mut trie = MerklePatriciaTrie::new();
         
-        trie.insert(b"key", b"value1".to_vec());
-        assert_eq!(trie.get(b"key"), Some(b"value1".to_vec()));
+        trie.insert(b"key", b"value1".to_vec()).unwrap();
+        assert_eq!(trie.get(b"key").unwrap(), Some(b"value1".to_vec()));
         
-        trie.insert(b"key", b"value2".to_vec());
-        assert_eq!(trie.get(b"key"), Some(b"value2".to_vec()));
+        trie.insert(b"key", b"value2".to_vec()).unwrap();
+        assert_eq!(trie.get(b"key").unwrap(), Some(b"value2".to_vec()));
     }
 
     #[test]
     fn test_multiple_keys_with_common_prefix() {
         let mut trie = MerklePatriciaTrie::new();
         
-        trie.insert(b"do", b"verb".to_vec());
-        trie.insert(b"dog", b"puppy".to_vec());
-        trie.insert(b"doge", b"coin".to_vec());
-        trie.insert(b"dodge", b"avoid".to_vec());
+        trie.insert(b"do", b"verb".to_vec()).unwrap();
+        trie.insert(b"dog", b"puppy".to_vec()).unwrap();
+        trie.insert(b"doge", b"coin".to_vec()).unwrap();
+        trie.insert(b"dodge", b"avoid".to_vec()).unwrap();
         
-        assert_eq!(trie.get(b"do"), Some(b"verb".to_vec()));
-        assert_eq!(trie.get(b"dog"), Some(b"puppy".to_vec()));
-        assert_eq!(trie.get(b"doge"), Some(b"coin".to_vec()));
-        assert_eq!(trie.get(b"dodge"), Some(b"avoid".to_vec()));
+        assert_eq!(trie.get(b"do").unwrap(), Some(b"verb".to_vec()));
+        assert_eq!(trie.get(b"dog").unwrap(), Some(b"puppy".to_vec()));
+        assert_eq!(trie.get(b"doge").unwrap(), Some(b"coin".to_vec()));
+        assert_eq!(trie.get(b"dodge").unwrap(), Some(b"avoid".to_vec()));
     }
 
     #[test]
     fn test_delete_single_key() {
         let mut trie = MerklePatriciaTrie::new();
         
-        trie.insert(b"key", b"value".to_vec());
-        assert_eq!(trie.get(b"key"), Some(b"value".to_vec()));
+        trie.insert(b"key", b"value".to_vec()).unwrap();
+        assert_eq!(trie.get(b"key").unwrap(), Some(b"value".to_vec()));
         
-        trie.delete(b"key");
-        assert_eq!(trie.get(b"key"), None);
+        trie.delete(b"key").unwrap();
+        assert_eq!(trie.get(b"key").unwrap(), None);
     }
 
     #[test]
     fn test_delete_with_multiple_keys() {
         let mut trie = MerklePatriciaTrie::new();
         
-        trie.insert(b"do", b"verb".to_vec());
-        trie.insert(b"dog", b"puppy".to_vec());
-        trie.insert(b"doge", b"coin".to_vec());
+        trie.insert(b"do", b"verb".to_vec()).unwrap();
+        trie.insert(b"dog", b"puppy".to_vec()).unwrap();
+        trie.insert(b"doge", b"coin".to_vec()).unwrap();
         
-        trie.delete(b"dog");
+        trie.delete(b"dog").unwrap();
         
-        assert_eq!(trie.get(b"do"), Some(b"verb".to_vec()));
-        assert_eq!(trie.get(b"dog"), None);
-        assert_eq!(trie.get(b"doge"), Some(b"coin".to_vec()));
+        assert_eq!(trie.get(b"do").unwrap(), Some(b"verb".to_vec()));
+        assert_eq!(trie.get(b"dog").unwrap(), None);
+        assert_eq!(trie.get(b"doge").unwrap(), Some(b"coin".to_vec()));
     }
 
     #[test]
@@ -131,11 +132,11 @@ mod tests {
         let mut trie2 = MerklePatriciaTrie::new();
         
         // Insert in same order
-        trie1.insert(b"do", b"verb".to_vec());
-        trie1.insert(b"dog", b"puppy".to_vec());
+        trie1.insert(b"do", b"verb".to_vec()).unwrap();
+        trie1.insert(b"dog", b"puppy".to_vec()).unwrap();
         
-        trie2.insert(b"do", b"verb".to_vec());
-        trie2.insert(b"dog", b"puppy".to_vec());
+        trie2.insert(b"do", b"verb".to_vec()).unwrap();
+        trie2.insert(b"dog", b"puppy".to_vec()).unwrap();
         
         // Root hashes should match
         assert_eq!(trie1.root_hash(), trie2.root_hash());
@@ -146,8 +147,8 @@ mod tests {
         let mut trie1 = MerklePatriciaTrie::new();
         let mut trie2 = MerklePatriciaTrie::new();
         
-        trie1.insert(b"key", b"value1".to_vec());
-        trie2.insert(b"key", b"value2".to_vec());
+        trie1.insert(b"key", b"value1".to_vec()).unwrap();
+        trie2.insert(b"key", b"value2".to_vec()).unwrap();
         
         assert_ne!(trie1.root_hash(), trie2.root_hash());
     }
@@ -156,25 +157,25 @@ mod tests {
     fn test_empty_trie_operations() {
         let mut trie = MerklePatriciaTrie::new();
         
-        assert_eq!(trie.get(b"any"), None);
+        assert_eq!(trie.get(b"any").unwrap(), None);
         
-        trie.delete(b"nonexistent");
-        assert_eq!(trie.get(b"nonexistent"), None);
+        trie.delete(b"nonexistent").unwrap();
+        assert_eq!(trie.get(b"nonexistent").unwrap(), None);
     }
 
     #[test]
     fn test_delete_and_reinsert() {
         let mut trie = MerklePatriciaTrie::new();
         
-        trie.insert(b"key", b"value1".to_vec());
+        trie.insert(b"key", b"value1".to_vec()).unwrap();
         let hash1 = trie.root_hash();
         
-        trie.delete(b"key");
-        trie.insert(b"key", b"value1".to_vec());
+        trie.delete(b"key").unwrap();
+        trie.insert(b"key", b"value1".to_vec()).unwrap();
         let hash2 = trie.root_hash();
         
         assert_eq!(hash1, hash2);
-        assert_eq!(trie.get(b"key"), Some(b"value1".to_vec()));
+        assert_eq!(trie.get(b"key").unwrap(), Some(b"value1".to_vec()));
     }
 
     #[test]
@@ -184,13 +185,13 @@ mod tests {
         for i in 0..100 {
             let key = format!("key{}", i);
             let value = format!("value{}", i);
-            trie.insert(key.as_bytes(), value.as_bytes().to_vec());
+            trie.insert(key.as_bytes(), value.as_bytes().to_vec()).unwrap();
         }
         
         for i in 0..100 {
             let key = format!("key{}", i);
             let value = format!("value{}", i);
-            assert_eq!(trie.get(key.as_bytes()), Some(value.as_bytes().to_vec()));
+            assert_eq!(trie.get(key.as_bytes()).unwrap(), Some(value.as_bytes().to_vec()));
         }
     }
 
@@ -198,14 +199,14 @@ mod tests {
     fn test_different_key_lengths() {
         let mut trie = MerklePatriciaTrie::new();
         
-        trie.insert(b"a", b"1".to_vec());
-        trie.insert(b"ab", b"2".to_vec());
-        trie.insert(b"abc", b"3".to_vec());
-        trie.insert(b"abcd", b"4".to_vec());
+        trie.insert(b"a", b"1".to_vec()).unwrap();
+        trie.insert(b"ab", b"2".to_vec()).unwrap();
+        trie.insert(b"abc", b"3".to_vec()).unwrap();
+        trie.insert(b"abcd", b"4".to_vec()).unwrap();
         
-        assert_eq!(trie.get(b"a"), Some(b"1".to_vec()));
-        assert_eq!(trie.get(b"ab"), Some(b"2".to_vec()));
-        assert_eq!(trie.get(b"abc"), Some(b"3".to_vec()));
-        assert_eq!(trie.get(b"abcd"), Some(b"4".to_vec()));
+        assert_eq!(trie.get(b"a").unwrap(), Some(b"1".to_vec()));
+        assert_eq!(trie.get(b"ab").unwrap(), Some(b"2".to_vec()));
+        assert_eq!(trie.get(b"abc").unwrap(), Some(b"3".to_vec()));
+        assert_eq!(trie.get(b"abcd").unwrap(), Some(b"4".to_vec()));
     }
 }
@@ -0,0 +1,231 @@
+use crate::db::NodeDb;
+use crate::error::MptError;
+use crate::nibbles::{bytes_to_nibbles, compact_decode};
+use crate::node::{ChildRef, Hash, Link, Node, TrieNode, EMPTY_ROOT, keccak256};
+use crate::proof::{ProofError, lookup};
+use crate::trie::MerklePatriciaTrie;
+use std::collections::{HashMap, HashSet};
+
+/// A Merkle proof for many keys at once
+///
+/// Holds the union of the nodes the keys' individual proofs would contain,
+/// each exactly once. Nodes are in depth-first order from the root, so every
+/// node is referenced by the root or by a node before it and the proof can be
+/// verified as it is received.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MultiProof {
+    /// RLP-encoded nodes, root first
+    pub nodes: Vec<Vec<u8>>,
+}
+
+impl MultiProof {
+    /// Returns the number of nodes in the proof
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns true if the proof contains no nodes
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+impl<D: NodeDb> MerklePatriciaTrie<D> {
+    /// Generates a single proof for all of `keys`, present or absent
+    ///
+    /// The trie is walked once, and nodes shared by several keys' paths, such
+    /// as the upper branches, are included only once.
+    pub fn prove_many<K: AsRef<[u8]>>(&self, keys: &[K]) -> Result<MultiProof, MptError> {
+        let mut paths: Vec<Vec<u8>> = keys.iter().map(|key| bytes_to_nibbles(key.as_ref())).collect();
+        paths.sort();
+        paths.dedup();
+        let paths: Vec<&[u8]> = paths.iter().map(Vec::as_slice).collect();
+
+        let mut proof = MultiProof::default();
+        self.prove_many_at(&paths, &self.root_ref(), &mut proof.nodes, &mut HashSet::new())?;
+        Ok(proof)
+    }
+
+    /// Helper: adds the nodes proving `paths` below `child` to `nodes`,
+    /// skipping any already in `seen`
+    ///
+    /// Follows the same rules as a single-key proof: only hash-referenced
+    /// nodes are included, and a walk ends at a leaf or where the path
+    /// diverges.
+    fn prove_many_at(
+        &self,
+        paths: &[&[u8]],
+        child: &Link,
+        nodes: &mut Vec<Vec<u8>>,
+        seen: &mut HashSet<Hash>,
+    ) -> Result<(), MptError> {
+        if paths.is_empty() {
+            return Ok(());
+        }
+        let node = match self.resolve(child)? {
+            TrieNode::Empty => return Ok(()),
+            n => n,
+        };
+        let encoded = node.encode_raw();
+        let included = match child {
+            Link::Stored(ChildRef::Hash(_)) => true,
+            Link::Stored(ChildRef::Inline(_)) => false,
+            // Hashed once committed if large enough; the root (the first
+            // node visited) always is
+            Link::Dirty(_) => encoded.len() >= 32 || nodes.is_empty(),
+        };
+        // Identical subtries can occur at several positions; one copy serves all
+        if included && seen.insert(keccak256(&encoded)) {
+            nodes.push(encoded);
+        }
+
+        match node {
+            TrieNode::Extension(encoded_path, ext_child) => {
+                let (ext_path, _) = compact_decode(&encoded_path)?;
+                let below: Vec<&[u8]> = paths
+                    .iter()
+                    .filter(|path| path.starts_with(&ext_path))
+                    .map(|path| &path[ext_path.len()..])
+                    .collect();
+                self.prove_many_at(&below, &ext_child, nodes, seen)?;
+            }
+
+            TrieNode::Branch(children, _) => {
+                for (idx, branch_child) in children.iter().enumerate() {
+                    let Some(branch_child) = branch_child else { continue };
+                    let below: Vec<&[u8]> = paths
+                        .iter()
+                        .filter_map(|path| path.split_first())
+                        .filter(|(nibble, _)| **nibble as usize == idx)
+                        .map(|(_, rest)| rest)
+                        .collect();
+                    self.prove_many_at(&below, branch_child, nodes, seen)?;
+                }
+            }
+
+            // Leaves end the walk whether or not the paths match
+            TrieNode::Empty | TrieNode::Leaf(..) => {}
+        }
+        Ok(())
+    }
+}
+
+/// Verifies a multiproof for `keys` against a trusted root hash
+///
+/// Returns the proven value of each key, in the order of `keys`, with `None`
+/// for a proven absence, under the same rules as `verify_proof`. Each node
+/// must be referenced by the root or by an earlier node, otherwise
+/// verification fails with `ProofError::UnexpectedNode`.
+pub fn verify_multiproof<K: AsRef<[u8]>>(
+    root: Hash,
+    keys: &[K],
+    proof: &MultiProof,
+) -> Result<Vec<Option<Vec<u8>>>, ProofError> {
+    if root == EMPTY_ROOT {
+        // The empty trie proves the absence of every key
+        return Ok(vec![None; keys.len()]);
+    }
+
+    let mut expected = HashSet::from([root]);
+    let mut nodes: HashMap<Hash, &[u8]> = HashMap::new();
+    for encoded in &proof.nodes {
+        let hash = keccak256(encoded);
+        if !expected.contains(&hash) {
+            return Err(ProofError::UnexpectedNode(hash));
+        }
+        expected.extend(Node::decode_raw(encoded)?.child_hashes());
+        nodes.insert(hash, encoded.as_slice());
+    }
+
+    keys.iter().map(|key| lookup(&nodes, root, key.as_ref())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proof::verify_proof;
+
+    /// A trie of storage-like slots with hashed keys and long values
+    fn slot_trie() -> MerklePatriciaTrie {
+        let mut trie = MerklePatriciaTrie::new();
+        for slot in 0..500u32 {
+            trie.insert(&keccak256(&slot.to_be_bytes()), keccak256(&slot.to_le_bytes()).to_vec()).unwrap();
+        }
+        trie
+    }
+
+    #[test]
+    fn test_multiproof_matches_single_proofs() {
+        let mut trie = slot_trie();
+        trie.commit().unwrap();
+        let root = trie.root_hash();
+
+        // Present slots, absent slots and a duplicate
+        let keys: Vec<Hash> = (0..200u32)
+            .step_by(3)
+            .chain(1000..1040)
+            .chain([0])
+            .map(|slot| keccak256(&slot.to_be_bytes()))
+            .collect();
+        let proof = trie.prove_many(&keys).unwrap();
+        let values = verify_multiproof(root, &keys, &proof).unwrap();
+
+        let mut separate = HashSet::new();
+        let mut separate_len = 0;
+        for (key, value) in keys.iter().zip(&values) {
+            let single = trie.prove(key).unwrap();
+            assert_eq!(verify_proof(root, key, &single).as_ref(), Ok(value));
+            separate_len += single.len();
+            separate.extend(single.nodes);
+        }
+        assert_eq!(values[0], Some(keccak256(&0u32.to_le_bytes()).to_vec()));
+        assert_eq!(values[70], None);
+
+        // Exactly the union of the single proofs, each node once
+        assert_eq!(proof.nodes.iter().cloned().collect::<HashSet<_>>(), separate);
+        assert_eq!(proof.len(), separate.len());
+        assert!(proof.len() < separate_len / 2);
+    }
+
+    #[test]
+    fn test_multiproof_of_uncommitted_changes() {
+        let mut trie = slot_trie();
+        trie.commit().unwrap();
+        trie.insert(b"new slot", vec![0xAB; 40]).unwrap();
+        let root = trie.root_hash();
+
+        let keys = [&b"new slot"[..], b"missing"];
+        let proof = trie.prove_many(&keys).unwrap();
+        assert_eq!(verify_multiproof(root, &keys, &proof), Ok(vec![Some(vec![0xAB; 40]), None]));
+
+        let empty = MerklePatriciaTrie::new();
+        let proof = empty.prove_many(&keys).unwrap();
+        assert!(proof.is_empty());
+        assert_eq!(verify_multiproof(EMPTY_ROOT, &keys, &proof), Ok(vec![None, None]));
+    }
+
+    #[test]
+    fn test_multiproof_rejects_tampering() {
+        let trie = slot_trie();
+        let root = trie.root_hash();
+        let keys: Vec<Hash> = (0..20u32).map(|slot| keccak256(&slot.to_be_bytes())).collect();
+        let proof = trie.prove_many(&keys).unwrap();
+
+        // A missing node leaves some key unproven
+        let mut truncated = proof.clone();
+        truncated.nodes.pop();
+        assert!(matches!(verify_multiproof(root, &keys, &truncated), Err(ProofError::MissingNode(_))));
+
+        // Nodes must arrive after the node referencing them
+        let mut reordered = proof.clone();
+        reordered.nodes.swap(0, 1);
+        assert!(matches!(verify_multiproof(root, &keys, &reordered), Err(ProofError::UnexpectedNode(_))));
+
+        // Unrelated nodes are rejected
+        let mut padded = proof.clone();
+        padded.nodes.push(Node::new_leaf(&[1, 2], vec![0; 40]).encode_raw());
+        assert!(matches!(verify_multiproof(root, &keys, &padded), Err(ProofError::UnexpectedNode(_))));
+
+        assert_eq!(verify_multiproof([0xAA; 32], &keys, &proof), Err(ProofError::UnexpectedNode(root)));
+    }
+}
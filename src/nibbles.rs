@@ -1,5 +1,7 @@
-/// Utility functions for working with nibbles (4-bit values) in Merkle Patricia Tries.
-/// In Ethereum's MPT, keys are represented as sequences of nibbles (hex digits).
+//! Utility functions for working with nibbles (4-bit values) in Merkle Patricia Tries.
+//! In Ethereum's MPT, keys are represented as sequences of nibbles (hex digits).
+
+use crate::error::MptError;
 
 /// Converts a byte slice into a vector of nibbles (each byte becomes 2 nibbles)
 pub fn bytes_to_nibbles(bytes: &[u8]) -> Vec<u8> {
@@ -12,14 +14,23 @@ pub fn bytes_to_nibbles(bytes: &[u8]) -> Vec<u8> {
 }
 
 /// Converts a vector of nibbles back to bytes
-/// Note: nibbles.len() must be even
-pub fn nibbles_to_bytes(nibbles: &[u8]) -> Vec<u8> {
-    assert!(nibbles.len() % 2 == 0, "Nibbles length must be even");
-    let mut bytes = Vec::with_capacity(nibbles.len() / 2);
-    for chunk in nibbles.chunks(2) {
-        bytes.push((chunk[0] << 4) | chunk[1]);
+/// Fails with `InvalidPath` if the length is odd or a value is not a nibble
+pub fn nibbles_to_bytes(nibbles: &[u8]) -> Result<Vec<u8>, MptError> {
+    if !nibbles.len().is_multiple_of(2) {
+        return Err(MptError::InvalidPath(format!("odd nibble count {}", nibbles.len())));
+    }
+    if let Some(bad) = nibbles.iter().find(|&&n| n > 0x0F) {
+        return Err(MptError::InvalidPath(format!("value {:#x} is not a nibble", bad)));
     }
-    bytes
+    Ok(pack_nibbles(nibbles))
+}
+
+/// Packs an even-length slice of valid nibbles into bytes
+fn pack_nibbles(nibbles: &[u8]) -> Vec<u8> {
+    nibbles
+        .chunks(2)
+        .map(|chunk| (chunk[0] << 4) | chunk[1])
+        .collect()
 }
 
 /// Compact encoding for nibble paths as used in Ethereum MPT.
@@ -48,13 +59,20 @@ pub fn compact_encode(nibbles: &[u8], is_leaf: bool) -> Vec<u8> {
         encoded.insert(0, flags);
     }
     
-    nibbles_to_bytes(&encoded)
+    pack_nibbles(&encoded)
 }
 
 /// Decodes a compact-encoded path back to nibbles and determines if it's a leaf
-pub fn compact_decode(encoded: &[u8]) -> (Vec<u8>, bool) {
+/// Fails with `InvalidEncoding` on empty input, an unknown flag or non-zero padding
+pub fn compact_decode(encoded: &[u8]) -> Result<(Vec<u8>, bool), MptError> {
     let nibbles = bytes_to_nibbles(encoded);
-    let first_nibble = nibbles[0];
+    let first_nibble = match nibbles.first() {
+        Some(&n) => n,
+        None => return Err(MptError::InvalidEncoding("empty compact path".into())),
+    };
+    if first_nibble > 3 {
+        return Err(MptError::InvalidEncoding(format!("unknown compact flag {:#x}", first_nibble)));
+    }
     
     let is_leaf = (first_nibble & 2) == 2;
     let is_odd = (first_nibble & 1) == 1;
@@ -62,10 +80,13 @@ pub fn compact_decode(encoded: &[u8]) -> (Vec<u8>, bool) {
     let result = if is_odd {
         nibbles[1..].to_vec()
     } else {
+        if nibbles[1] != 0 {
+            return Err(MptError::InvalidEncoding("non-zero compact padding".into()));
+        }
         nibbles[2..].to_vec()
     };
     
-    (result, is_leaf)
+    Ok((result, is_leaf))
 }
 
 /// Finds the common prefix length between two nibble slices
@@ -88,8 +109,14 @@ mod tests {
 
     #[test]
     fn test_nibbles_to_bytes() {
-        assert_eq!(nibbles_to_bytes(&[10, 11, 12, 13]), vec![0xAB, 0xCD]);
-        assert_eq!(nibbles_to_bytes(&[1, 2, 3, 4]), vec![0x12, 0x34]);
+        assert_eq!(nibbles_to_bytes(&[10, 11, 12, 13]), Ok(vec![0xAB, 0xCD]));
+        assert_eq!(nibbles_to_bytes(&[1, 2, 3, 4]), Ok(vec![0x12, 0x34]));
+    }
+
+    #[test]
+    fn test_nibbles_to_bytes_invalid() {
+        assert!(matches!(nibbles_to_bytes(&[1, 2, 3]), Err(MptError::InvalidPath(_))));
+        assert!(matches!(nibbles_to_bytes(&[1, 16]), Err(MptError::InvalidPath(_))));
     }
 
     #[test]
@@ -122,15 +149,22 @@ mod tests {
 
     #[test]
     fn test_compact_decode() {
-        let (nibbles, is_leaf) = compact_decode(&[0x20, 0x12, 0x34]);
+        let (nibbles, is_leaf) = compact_decode(&[0x20, 0x12, 0x34]).unwrap();
         assert_eq!(nibbles, vec![1, 2, 3, 4]);
         assert!(is_leaf);
 
-        let (nibbles, is_leaf) = compact_decode(&[0x00, 0x12, 0x34]);
+        let (nibbles, is_leaf) = compact_decode(&[0x00, 0x12, 0x34]).unwrap();
         assert_eq!(nibbles, vec![1, 2, 3, 4]);
         assert!(!is_leaf);
     }
 
+    #[test]
+    fn test_compact_decode_invalid() {
+        assert!(matches!(compact_decode(&[]), Err(MptError::InvalidEncoding(_))));
+        assert!(matches!(compact_decode(&[0x40, 0x12]), Err(MptError::InvalidEncoding(_))));
+        assert!(matches!(compact_decode(&[0x01, 0x12]), Err(MptError::InvalidEncoding(_))));
+    }
+
     #[test]
     fn test_common_prefix_len() {
         assert_eq!(common_prefix_len(&[1, 2, 3], &[1, 2, 4]), 2);
@@ -1,10 +1,44 @@
 use crate::nibbles::{compact_encode, compact_decode};
 use rlp::{Encodable, Decodable, RlpStream, Rlp, DecoderError};
+use std::sync::Arc;
 use tiny_keccak::{Hasher, Keccak};
 
 /// Hash type used in the trie (32 bytes)
 pub type Hash = [u8; 32];
 
+/// Root hash of an empty trie: `keccak256(rlp(""))`
+///
+/// This is the `storageRoot` of every Ethereum account without storage.
+pub const EMPTY_ROOT: Hash = [
+    0x56, 0xe8, 0x1f, 0x17, 0x1b, 0xcc, 0x55, 0xa6, 0xff, 0x83, 0x45, 0xe6, 0x92, 0xc0, 0xf8, 0x6e,
+    0x5b, 0x48, 0xe0, 0x1b, 0x99, 0x6c, 0xad, 0xc0, 0x01, 0x62, 0x2f, 0xb5, 0xe3, 0x63, 0xb4, 0x21,
+];
+
+/// A reference from a parent node to one of its children
+///
+/// Following the Ethereum specification, a child whose RLP encoding is at
+/// least 32 bytes long is referenced by its Keccak-256 hash, while a shorter
+/// child is embedded directly in its parent.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChildRef {
+    /// Hash of the child's RLP encoding
+    Hash(Hash),
+    /// The child node itself, inlined because its encoding is under 32 bytes
+    Inline(Box<Node>),
+}
+
+impl ChildRef {
+    /// Builds the reference a parent should hold for `node`
+    pub fn from_node(node: Node) -> Self {
+        let encoded = node.encode_raw();
+        if encoded.len() < 32 {
+            ChildRef::Inline(Box::new(node))
+        } else {
+            ChildRef::Hash(keccak256(&encoded))
+        }
+    }
+}
+
 /// Represents the different types of nodes in a Merkle Patricia Trie
 #[derive(Debug, Clone, PartialEq)]
 pub enum Node {
@@ -16,12 +50,12 @@ pub enum Node {
     Leaf(Vec<u8>, Vec<u8>),
     
     /// Extension node: stores a shared path prefix and a reference to the next node
-    /// (encoded_path, child_hash)
-    Extension(Vec<u8>, Hash),
+    /// (encoded_path, child)
+    Extension(Vec<u8>, ChildRef),
     
     /// Branch node: 16 children (one for each hex digit) + optional value
     /// ([child_0, ..., child_15], optional_value)
-    Branch(Box<[Option<Hash>; 16]>, Option<Vec<u8>>),
+    Branch(Box<[Option<ChildRef>; 16]>, Option<Vec<u8>>),
 }
 
 impl Node {
@@ -31,15 +65,15 @@ impl Node {
         Node::Leaf(encoded, value)
     }
     
-    /// Creates a new extension node from nibbles and child hash
-    pub fn new_extension(nibbles: &[u8], child: Hash) -> Self {
+    /// Creates a new extension node from nibbles and child reference
+    pub fn new_extension(nibbles: &[u8], child: ChildRef) -> Self {
         let encoded = compact_encode(nibbles, false);
         Node::Extension(encoded, child)
     }
     
     /// Creates a new empty branch node
     pub fn new_branch() -> Self {
-        Node::Branch(Box::new([None; 16]), None)
+        Node::Branch(Box::default(), None)
     }
     
     /// Computes the hash of this node using Keccak-256
@@ -60,6 +94,24 @@ impl Node {
         let rlp = Rlp::new(data);
         Self::decode(&rlp)
     }
+
+    /// Returns the hashes of the stored nodes this node references, looking
+    /// through inlined children
+    pub(crate) fn child_hashes(&self) -> Vec<Hash> {
+        let children: Vec<&ChildRef> = match self {
+            Node::Extension(_, child) => vec![child],
+            Node::Branch(children, _) => children.iter().flatten().collect(),
+            Node::Empty | Node::Leaf(..) => return Vec::new(),
+        };
+        let mut hashes = Vec::new();
+        for child in children {
+            match child {
+                ChildRef::Hash(hash) => hashes.push(*hash),
+                ChildRef::Inline(node) => hashes.extend(node.child_hashes()),
+            }
+        }
+        hashes
+    }
 }
 
 impl Encodable for Node {
@@ -76,13 +128,13 @@ impl Encodable for Node {
             Node::Extension(path, child) => {
                 stream.begin_list(2);
                 stream.append(path);
-                stream.append(&child.as_ref());
+                append_child(stream, child);
             }
             Node::Branch(children, value) => {
                 stream.begin_list(17);
                 for child in children.iter() {
                     match child {
-                        Some(hash) => stream.append(&hash.as_ref()),
+                        Some(child) => append_child(stream, child),
                         None => stream.append_empty_data(),
                     };
                 }
@@ -95,6 +147,34 @@ impl Encodable for Node {
     }
 }
 
+/// Appends a child reference: a hash as a 32-byte string, an inline node as
+/// its raw RLP list
+fn append_child<'a>(stream: &'a mut RlpStream, child: &ChildRef) -> &'a mut RlpStream {
+    match child {
+        ChildRef::Hash(hash) => stream.append(&hash.as_ref()),
+        ChildRef::Inline(node) => stream.append_raw(&node.encode_raw(), 1),
+    }
+}
+
+impl Decodable for ChildRef {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.is_list() {
+            if rlp.as_raw().len() >= 32 {
+                return Err(DecoderError::Custom("Inline node too long"));
+            }
+            return Ok(ChildRef::Inline(Box::new(Node::decode(rlp)?)));
+        }
+        
+        let data: Vec<u8> = rlp.as_val()?;
+        if data.len() != 32 {
+            return Err(DecoderError::Custom("Invalid hash length"));
+        }
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(&data);
+        Ok(ChildRef::Hash(hash))
+    }
+}
+
 impl Decodable for Node {
     fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
         if rlp.is_empty() {
@@ -106,32 +186,23 @@ impl Decodable for Node {
         match item_count {
             2 => {
                 let path: Vec<u8> = rlp.val_at(0)?;
-                let (_, is_leaf) = compact_decode(&path);
+                let (_, is_leaf) = compact_decode(&path)
+                    .map_err(|_| DecoderError::Custom("Invalid compact path"))?;
                 
                 if is_leaf {
                     let value: Vec<u8> = rlp.val_at(1)?;
                     Ok(Node::Leaf(path, value))
                 } else {
-                    let child: Vec<u8> = rlp.val_at(1)?;
-                    if child.len() != 32 {
-                        return Err(DecoderError::Custom("Invalid hash length"));
-                    }
-                    let mut hash = [0u8; 32];
-                    hash.copy_from_slice(&child);
-                    Ok(Node::Extension(path, hash))
+                    let child: ChildRef = rlp.val_at(1)?;
+                    Ok(Node::Extension(path, child))
                 }
             }
             17 => {
-                let mut children = Box::new([None; 16]);
-                for i in 0..16 {
-                    let child_data: Vec<u8> = rlp.val_at(i)?;
-                    if !child_data.is_empty() {
-                        if child_data.len() != 32 {
-                            return Err(DecoderError::Custom("Invalid hash length in branch"));
-                        }
-                        let mut hash = [0u8; 32];
-                        hash.copy_from_slice(&child_data);
-                        children[i] = Some(hash);
+                let mut children: Box<[Option<ChildRef>; 16]> = Box::default();
+                for (i, child) in children.iter_mut().enumerate() {
+                    let child_rlp = rlp.at(i)?;
+                    if !child_rlp.is_empty() {
+                        *child = Some(child_rlp.as_val()?);
                     }
                 }
                 
@@ -149,6 +220,90 @@ impl Decodable for Node {
     }
 }
 
+/// A node as a trie holds it in memory
+///
+/// Mirrors `Node`, except that children changed since the last commit are
+/// kept as `Link::Dirty` until the commit hashes and stores them. Only the
+/// trie builds these: anything decoded is a `Node`, converted on load.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum TrieNode {
+    Empty,
+    Leaf(Vec<u8>, Vec<u8>),
+    Extension(Vec<u8>, Link),
+    Branch(Box<[Option<Link>; 16]>, Option<Vec<u8>>),
+}
+
+/// A reference from an in-memory node to one of its children
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Link {
+    /// A child as committed: hashed or inlined
+    Stored(ChildRef),
+    /// A modified child, not yet hashed or stored
+    Dirty(Arc<TrieNode>),
+}
+
+impl Link {
+    /// Refers to a stored node by hash
+    pub(crate) fn stored(hash: Hash) -> Self {
+        Link::Stored(ChildRef::Hash(hash))
+    }
+}
+
+impl TrieNode {
+    /// Creates a new leaf node from nibbles and value
+    pub(crate) fn new_leaf(nibbles: &[u8], value: Vec<u8>) -> Self {
+        TrieNode::Leaf(compact_encode(nibbles, true), value)
+    }
+
+    /// Creates a new extension node from nibbles and child reference
+    pub(crate) fn new_extension(nibbles: &[u8], child: Link) -> Self {
+        TrieNode::Extension(compact_encode(nibbles, false), child)
+    }
+
+    /// Creates a new empty branch node
+    pub(crate) fn new_branch() -> Self {
+        TrieNode::Branch(Box::default(), None)
+    }
+
+    /// Returns the node as it will be committed, with each dirty child
+    /// hashed or inlined according to its size
+    pub(crate) fn to_node(&self) -> Node {
+        let child_ref = |link: &Link| match link {
+            Link::Stored(child) => child.clone(),
+            Link::Dirty(node) => ChildRef::from_node(node.to_node()),
+        };
+        match self {
+            TrieNode::Empty => Node::Empty,
+            TrieNode::Leaf(path, value) => Node::Leaf(path.clone(), value.clone()),
+            TrieNode::Extension(path, child) => Node::Extension(path.clone(), child_ref(child)),
+            TrieNode::Branch(children, value) => {
+                Node::Branch(Box::new(children.each_ref().map(|child| child.as_ref().map(child_ref))), value.clone())
+            }
+        }
+    }
+
+    /// Encodes the node as it will be committed
+    pub(crate) fn encode_raw(&self) -> Vec<u8> {
+        self.to_node().encode_raw()
+    }
+
+    /// Computes the hash the node will be committed under
+    pub(crate) fn hash(&self) -> Hash {
+        keccak256(&self.encode_raw())
+    }
+}
+
+impl From<Node> for TrieNode {
+    fn from(node: Node) -> Self {
+        match node {
+            Node::Empty => TrieNode::Empty,
+            Node::Leaf(path, value) => TrieNode::Leaf(path, value),
+            Node::Extension(path, child) => TrieNode::Extension(path, Link::Stored(child)),
+            Node::Branch(children, value) => TrieNode::Branch(Box::new(children.map(|child| child.map(Link::Stored))), value),
+        }
+    }
+}
+
 /// Computes Keccak-256 hash of the input
 pub fn keccak256(data: &[u8]) -> Hash {
     let mut hasher = Keccak::v256();
@@ -173,7 +328,7 @@ mod tests {
     #[test]
     fn test_extension_encoding() {
         let hash = [0u8; 32];
-        let node = Node::new_extension(&[1, 2, 3, 4], hash);
+        let node = Node::new_extension(&[1, 2, 3, 4], ChildRef::Hash(hash));
         let encoded = node.encode_raw();
         let decoded = Node::decode_raw(&encoded).unwrap();
         assert_eq!(node, decoded);
@@ -183,8 +338,8 @@ mod tests {
     fn test_branch_encoding() {
         let mut node = Node::new_branch();
         if let Node::Branch(ref mut children, ref mut value) = node {
-            children[0] = Some([1u8; 32]);
-            children[15] = Some([2u8; 32]);
+            children[0] = Some(ChildRef::Hash([1u8; 32]));
+            children[15] = Some(ChildRef::Hash([2u8; 32]));
             *value = Some(b"branch_value".to_vec());
         }
         let encoded = node.encode_raw();
@@ -192,6 +347,50 @@ mod tests {
         assert_eq!(node, decoded);
     }
 
+    #[test]
+    fn test_inline_child_encoding() {
+        let leaf = Node::new_leaf(&[5], b"v".to_vec());
+        let child = ChildRef::from_node(leaf.clone());
+        assert_eq!(child, ChildRef::Inline(Box::new(leaf)));
+
+        let mut node = Node::new_branch();
+        if let Node::Branch(ref mut children, _) = node {
+            children[3] = Some(child);
+            children[9] = Some(ChildRef::Hash([7u8; 32]));
+        }
+        let encoded = node.encode_raw();
+        let decoded = Node::decode_raw(&encoded).unwrap();
+        assert_eq!(node, decoded);
+    }
+
+    #[test]
+    fn test_large_child_is_hashed() {
+        let leaf = Node::new_leaf(&[1, 2, 3, 4], vec![0xAB; 40]);
+        let child = ChildRef::from_node(leaf.clone());
+        assert_eq!(child, ChildRef::Hash(leaf.hash()));
+    }
+
+    #[test]
+    fn test_dirty_children_commit_by_size() {
+        let small = Node::new_leaf(&[5], b"v".to_vec());
+        let large = Node::new_leaf(&[1, 2, 3, 4], vec![0xAB; 40]);
+        let mut node = TrieNode::new_branch();
+        if let TrieNode::Branch(ref mut children, _) = node {
+            children[3] = Some(Link::Dirty(Arc::new(small.clone().into())));
+            children[9] = Some(Link::Dirty(Arc::new(large.clone().into())));
+        }
+
+        let committed = node.to_node();
+        let mut expected = Node::new_branch();
+        if let Node::Branch(ref mut children, _) = expected {
+            children[3] = Some(ChildRef::Inline(Box::new(small)));
+            children[9] = Some(ChildRef::Hash(large.hash()));
+        }
+        assert_eq!(committed, expected);
+        assert_eq!(node.hash(), expected.hash());
+        assert_eq!(TrieNode::from(Node::decode_raw(&node.encode_raw()).unwrap()).to_node(), expected);
+    }
+
     #[test]
     fn test_empty_node() {
         let node = Node::Empty;
@@ -200,6 +399,20 @@ mod tests {
         assert_eq!(node, decoded);
     }
 
+    #[test]
+    fn test_invalid_path_rejected() {
+        let mut stream = RlpStream::new_list(2);
+        stream.append(&vec![0x40u8, 0x12]);
+        stream.append(&b"value".to_vec());
+        assert!(Node::decode_raw(&stream.out()).is_err());
+    }
+
+    #[test]
+    fn test_empty_root() {
+        assert_eq!(EMPTY_ROOT, keccak256(&rlp::NULL_RLP));
+        assert_eq!(EMPTY_ROOT, Node::Empty.hash());
+    }
+
     #[test]
     fn test_keccak256() {
         let data = b"hello world";
@@ -0,0 +1,173 @@
+use crate::error::MptError;
+use crate::node::Hash;
+use crate::proof::MerkleProof;
+use crate::trie::MerklePatriciaTrie;
+
+/// Builder for a trie of items keyed by their position, as used for a
+/// block's transactions and receipts
+///
+/// Item `i` is stored under `rlp(i)`. Items are stored exactly as given:
+/// legacy transactions and receipts are RLP lists, while EIP-2718 typed ones
+/// are their envelope bytes `type || payload`, without any extra RLP wrapping.
+pub struct OrderedTrie {
+    trie: MerklePatriciaTrie,
+    len: usize,
+}
+
+/// Builder for a block's transaction trie, whose root is `transactionsRoot`
+pub type TransactionTrie = OrderedTrie;
+
+/// Builder for a block's receipt trie, whose root is `receiptsRoot`
+pub type ReceiptTrie = OrderedTrie;
+
+impl OrderedTrie {
+    /// Creates an empty builder
+    pub fn new() -> Self {
+        Self { trie: MerklePatriciaTrie::new(), len: 0 }
+    }
+
+    /// Appends an encoded item at the next index
+    pub fn push(&mut self, item: Vec<u8>) -> Result<(), MptError> {
+        self.trie.insert(&index_key(self.len), item)?;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Returns the number of items
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if no items were added
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the encoded item at `index`
+    pub fn get(&self, index: usize) -> Result<Option<Vec<u8>>, MptError> {
+        self.trie.get(&index_key(index))
+    }
+
+    /// Returns the root hash over all items added so far
+    pub fn root_hash(&self) -> Hash {
+        self.trie.root_hash()
+    }
+
+    /// Generates a proof for the item at `index`
+    ///
+    /// Verify it with `verify_proof(root, &index_key(index), &proof)`.
+    pub fn prove(&self, index: usize) -> Result<MerkleProof, MptError> {
+        self.trie.prove(&index_key(index))
+    }
+
+    /// Returns the underlying trie
+    pub fn trie(&self) -> &MerklePatriciaTrie {
+        &self.trie
+    }
+}
+
+impl Default for OrderedTrie {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the trie key of the item at `index`: its RLP encoding
+pub fn index_key(index: usize) -> Vec<u8> {
+    rlp::encode(&(index as u64)).to_vec()
+}
+
+/// Computes the root of the trie holding `items` keyed by their index
+///
+/// This is the `transactionsRoot` or `receiptsRoot` of a block when given its
+/// encoded transactions or receipts in order.
+pub fn ordered_trie_root<I, T>(items: I) -> Result<Hash, MptError>
+where
+    I: IntoIterator<Item = T>,
+    T: AsRef<[u8]>,
+{
+    let mut trie = OrderedTrie::new();
+    for item in items {
+        trie.push(item.as_ref().to_vec())?;
+    }
+    Ok(trie.root_hash())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::EMPTY_ROOT;
+    use crate::proof::verify_proof;
+    use rlp::RlpStream;
+
+    /// Builds a stand-in for an encoded legacy transaction
+    fn legacy_item(nonce: u64) -> Vec<u8> {
+        let mut stream = RlpStream::new_list(3);
+        stream.append(&nonce).append(&21000u64).append(&vec![0xAB; 20]);
+        stream.out().to_vec()
+    }
+
+    #[test]
+    fn test_index_keys() {
+        assert_eq!(index_key(0), vec![0x80]);
+        assert_eq!(index_key(1), vec![0x01]);
+        assert_eq!(index_key(127), vec![0x7f]);
+        assert_eq!(index_key(128), vec![0x81, 0x80]);
+        assert_eq!(index_key(256), vec![0x82, 0x01, 0x00]);
+    }
+
+    #[test]
+    fn test_empty_block_root() {
+        assert_eq!(ordered_trie_root(Vec::<Vec<u8>>::new()).unwrap(), EMPTY_ROOT);
+        assert!(TransactionTrie::new().is_empty());
+    }
+
+    #[test]
+    fn test_known_ordered_root() {
+        // Reference vector shared by other Ethereum trie implementations
+        let root = ordered_trie_root([&b"doe"[..], b"reindeer"]).unwrap();
+        assert_eq!(hex::encode(root), "e766d5d51b89dc39d981b41bda63248d7abce4f0225eefd023792a540bcffee3");
+    }
+
+    #[test]
+    fn test_root_matches_manual_trie() {
+        // Mix legacy items with EIP-2718 typed envelopes (type 0x02)
+        let items: Vec<Vec<u8>> = (0..200u64)
+            .map(|i| if i % 3 == 0 { [&[0x02][..], &legacy_item(i)].concat() } else { legacy_item(i) })
+            .collect();
+
+        let mut manual = MerklePatriciaTrie::new();
+        for (i, item) in items.iter().enumerate() {
+            manual.insert(&rlp::encode(&(i as u64)), item.clone()).unwrap();
+        }
+
+        let mut builder = ReceiptTrie::new();
+        for item in &items {
+            builder.push(item.clone()).unwrap();
+        }
+        assert_eq!(builder.len(), 200);
+        assert_eq!(builder.root_hash(), manual.root_hash());
+        assert_eq!(ordered_trie_root(&items).unwrap(), manual.root_hash());
+
+        // Typed envelopes are stored verbatim
+        assert_eq!(builder.get(3).unwrap(), Some(items[3].clone()));
+        assert_eq!(builder.get(3).unwrap().unwrap()[0], 0x02);
+        assert_eq!(builder.get(200).unwrap(), None);
+    }
+
+    #[test]
+    fn test_prove_item() {
+        let mut builder = TransactionTrie::new();
+        for i in 0..130u64 {
+            builder.push(legacy_item(i)).unwrap();
+        }
+        let root = builder.root_hash();
+        for index in [0, 1, 127, 128, 129] {
+            let proof = builder.prove(index).unwrap();
+            assert_eq!(
+                verify_proof(root, &index_key(index), &proof),
+                Ok(Some(legacy_item(index as u64)))
+            );
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use crate::node::{Node, Hash, ChildRef, EMPTY_ROOT, keccak256};
+use crate::nibbles::{bytes_to_nibbles, compact_decode};
+use rlp::DecoderError;
+use std::collections::HashMap;
+use std::fmt;
+
+/// A Merkle proof for a single key.
+///
+/// Holds the RLP-encoded nodes on the path from the root towards the key,
+/// ordered from the root downwards. Together with a trusted root hash this is
+/// all a verifier needs; the rest of the trie is not required.
+///
+/// The same structure serves as an inclusion proof (the path ends at the
+/// key's value) and as an exclusion proof (the path ends where the key
+/// diverges from every stored key).
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MerkleProof {
+    /// RLP-encoded nodes, root first
+    pub nodes: Vec<Vec<u8>>,
+}
+
+impl MerkleProof {
+    /// Creates a proof from a list of RLP-encoded nodes
+    pub fn new(nodes: Vec<Vec<u8>>) -> Self {
+        Self { nodes }
+    }
+
+    /// Returns the number of nodes in the proof
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns true if the proof contains no nodes
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+/// Errors that can occur while verifying a proof
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProofError {
+    /// A node on the path to the key is not part of the proof
+    MissingNode(Hash),
+    /// A proof node is not a valid RLP-encoded trie node
+    InvalidNode(DecoderError),
+    /// A multiproof node is not referenced by the root or an earlier node
+    UnexpectedNode(Hash),
+}
+
+impl fmt::Display for ProofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProofError::MissingNode(hash) => {
+                write!(f, "proof is missing node 0x{}", hex::encode(hash))
+            }
+            ProofError::InvalidNode(err) => write!(f, "invalid proof node: {}", err),
+            ProofError::UnexpectedNode(hash) => {
+                write!(f, "proof node 0x{} is not referenced by an earlier node", hex::encode(hash))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProofError {}
+
+impl From<DecoderError> for ProofError {
+    fn from(err: DecoderError) -> Self {
+        ProofError::InvalidNode(err)
+    }
+}
+
+/// Verifies a proof for `key` against a trusted root hash.
+///
+/// Every node is looked up by the Keccak-256 hash of its encoding, so a proof
+/// can only be followed along references that are committed to by `root`.
+///
+/// Returns `Ok(Some(value))` for a proven inclusion and `Ok(None)` only when
+/// the proof shows where the key diverges from the trie: an empty branch slot,
+/// a branch without a value at the end of the key, or an extension or leaf
+/// whose path does not match. A proof that stops before such a point fails
+/// with `ProofError::MissingNode`.
+pub fn verify_proof(root: Hash, key: &[u8], proof: &MerkleProof) -> Result<Option<Vec<u8>>, ProofError> {
+    if root == EMPTY_ROOT {
+        // The empty trie proves the absence of every key
+        return Ok(None);
+    }
+    
+    let nodes: HashMap<Hash, &[u8]> = proof
+        .nodes
+        .iter()
+        .map(|encoded| (keccak256(encoded), encoded.as_slice()))
+        .collect();
+    lookup(&nodes, root, key)
+}
+
+/// Helper: follows `key` from `root` through proof nodes indexed by hash
+pub(crate) fn lookup(nodes: &HashMap<Hash, &[u8]>, root: Hash, key: &[u8]) -> Result<Option<Vec<u8>>, ProofError> {
+    let nibbles = bytes_to_nibbles(key);
+    let mut path = nibbles.as_slice();
+    let mut next = ChildRef::Hash(root);
+
+    loop {
+        let node = match next {
+            ChildRef::Hash(hash) => {
+                let encoded = nodes.get(&hash).ok_or(ProofError::MissingNode(hash))?;
+                Node::decode_raw(encoded)?
+            }
+            // Inlined nodes were covered by their parent's hash
+            ChildRef::Inline(node) => *node,
+        };
+
+        match node {
+            Node::Empty => return Ok(None),
+
+            Node::Leaf(encoded_path, value) => {
+                let leaf_path = decode_path(&encoded_path)?;
+                return Ok(if leaf_path == path { Some(value) } else { None });
+            }
+
+            Node::Extension(encoded_path, child) => {
+                let ext_path = decode_path(&encoded_path)?;
+                if !path.starts_with(&ext_path) {
+                    return Ok(None);
+                }
+                path = &path[ext_path.len()..];
+                next = child;
+            }
+
+            Node::Branch(mut children, branch_value) => {
+                if path.is_empty() {
+                    return Ok(branch_value);
+                }
+                match children[path[0] as usize].take() {
+                    Some(child) => {
+                        path = &path[1..];
+                        next = child;
+                    }
+                    None => return Ok(None),
+                }
+            }
+        }
+    }
+}
+
+/// Helper: decodes a node's compact path, rejecting malformed encodings
+fn decode_path(encoded_path: &[u8]) -> Result<Vec<u8>, ProofError> {
+    compact_decode(encoded_path)
+        .map(|(path, _)| path)
+        .map_err(|_| ProofError::InvalidNode(DecoderError::Custom("Invalid compact path")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MerklePatriciaTrie;
+
+    fn sample_trie() -> MerklePatriciaTrie {
+        let mut trie = MerklePatriciaTrie::new();
+        trie.insert(b"do", b"verb".to_vec()).unwrap();
+        trie.insert(b"dog", b"puppy".to_vec()).unwrap();
+        trie.insert(b"doge", b"coin".to_vec()).unwrap();
+        trie.insert(b"horse", b"stallion".to_vec()).unwrap();
+        trie
+    }
+
+    #[test]
+    fn test_prove_and_verify() {
+        let trie = sample_trie();
+        let root = trie.root_hash();
+
+        for (key, value) in [
+            (&b"do"[..], &b"verb"[..]),
+            (b"dog", b"puppy"),
+            (b"doge", b"coin"),
+            (b"horse", b"stallion"),
+        ] {
+            let proof = trie.prove(key).unwrap();
+            assert!(!proof.is_empty());
+            assert_eq!(verify_proof(root, key, &proof), Ok(Some(value.to_vec())));
+        }
+    }
+
+    #[test]
+    fn test_exclusion_empty_branch_slot() {
+        let trie = sample_trie();
+        // All keys share the nibble 6; "cat" (0x63) hits an empty slot in the
+        // branch below the root extension
+        let proof = trie.prove(b"cat").unwrap();
+        assert_eq!(proof.len(), 2);
+        assert_eq!(verify_proof(trie.root_hash(), b"cat", &proof), Ok(None));
+    }
+
+    #[test]
+    fn test_exclusion_mismatched_extension() {
+        let mut trie = MerklePatriciaTrie::new();
+        trie.insert(b"dog", b"puppy".to_vec()).unwrap();
+        trie.insert(b"doge", b"coin".to_vec()).unwrap();
+        let root = trie.root_hash();
+
+        let proof = trie.prove(b"cow").unwrap();
+        assert_eq!(verify_proof(root, b"cow", &proof), Ok(None));
+    }
+
+    #[test]
+    fn test_exclusion_mismatched_leaf() {
+        let mut trie = MerklePatriciaTrie::new();
+        trie.insert(b"dog", b"puppy".to_vec()).unwrap();
+        let root = trie.root_hash();
+
+        let proof = trie.prove(b"dot").unwrap();
+        assert_eq!(proof.len(), 1);
+        assert_eq!(verify_proof(root, b"dot", &proof), Ok(None));
+    }
+
+    #[test]
+    fn test_exclusion_branch_without_value() {
+        let mut trie = MerklePatriciaTrie::new();
+        trie.insert(b"dog", b"puppy".to_vec()).unwrap();
+        trie.insert(b"doge", b"coin".to_vec()).unwrap();
+        trie.insert(b"dogs", b"pack".to_vec()).unwrap();
+        trie.delete(b"dog").unwrap();
+        let root = trie.root_hash();
+
+        // "dog" now ends on a branch that holds no value
+        let proof = trie.prove(b"dog").unwrap();
+        assert_eq!(verify_proof(root, b"dog", &proof), Ok(None));
+    }
+
+    #[test]
+    fn test_exclusion_empty_trie() {
+        let trie = MerklePatriciaTrie::new();
+        let proof = trie.prove(b"anything").unwrap();
+        assert!(proof.is_empty());
+        assert_eq!(verify_proof(trie.root_hash(), b"anything", &proof), Ok(None));
+    }
+
+    #[test]
+    fn test_exclusion_requires_divergence() {
+        // Long values keep every node hash-referenced rather than inlined
+        let mut trie = MerklePatriciaTrie::new();
+        for key in [&b"do"[..], b"dog", b"doge", b"horse"] {
+            trie.insert(key, vec![0xAB; 40]).unwrap();
+        }
+        let root = trie.root_hash();
+
+        // A truncated path cannot be passed off as an absence proof
+        let mut proof = trie.prove(b"doge").unwrap();
+        proof.nodes.pop();
+        assert!(matches!(verify_proof(root, b"doge", &proof), Err(ProofError::MissingNode(_))));
+
+        // Nor can an exclusion proof for one key be reused for a deeper key
+        let proof = trie.prove(b"dot").unwrap();
+        assert!(matches!(verify_proof(root, b"doge", &proof), Err(ProofError::MissingNode(_))));
+    }
+
+    #[test]
+    fn test_verify_wrong_root() {
+        let trie = sample_trie();
+        let proof = trie.prove(b"dog").unwrap();
+        let wrong_root = [0xAAu8; 32];
+        assert_eq!(
+            verify_proof(wrong_root, b"dog", &proof),
+            Err(ProofError::MissingNode(wrong_root))
+        );
+    }
+
+    #[test]
+    fn test_verify_tampered_proof() {
+        let trie = sample_trie();
+        let root = trie.root_hash();
+        let mut proof = trie.prove(b"doge").unwrap();
+
+        // Dropping the last node breaks the hash chain
+        proof.nodes.pop();
+        assert!(matches!(
+            verify_proof(root, b"doge", &proof),
+            Err(ProofError::MissingNode(_))
+        ));
+    }
+
+    #[test]
+    fn test_proof_after_update() {
+        let mut trie = sample_trie();
+        let old_root = trie.root_hash();
+        let old_proof = trie.prove(b"dog").unwrap();
+
+        trie.insert(b"dog", b"animal".to_vec()).unwrap();
+        let new_root = trie.root_hash();
+        let new_proof = trie.prove(b"dog").unwrap();
+
+        assert_eq!(verify_proof(old_root, b"dog", &old_proof), Ok(Some(b"puppy".to_vec())));
+        assert_eq!(verify_proof(new_root, b"dog", &new_proof), Ok(Some(b"animal".to_vec())));
+        assert!(verify_proof(new_root, b"dog", &old_proof).is_err());
+    }
+}
@@ -0,0 +1,366 @@
+use crate::error::MptError;
+use crate::header::{BlockHeader, fixed_at};
+use crate::node::Hash;
+use crate::ordered::index_key;
+use crate::proof::{MerkleProof, ProofError, verify_proof};
+use crate::state::Address;
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+use std::fmt;
+
+/// A log (event) emitted during a transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Log {
+    /// Contract that emitted the log
+    pub address: Address,
+    /// Indexed event parameters; the first is usually the event signature hash
+    pub topics: Vec<Hash>,
+    /// Non-indexed event parameters
+    pub data: Vec<u8>,
+}
+
+impl Encodable for Log {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(3);
+        s.append(&self.address.as_slice());
+        s.begin_list(self.topics.len());
+        for topic in &self.topics {
+            s.append(&topic.as_slice());
+        }
+        s.append(&self.data);
+    }
+}
+
+impl Decodable for Log {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 3 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let topics_rlp = rlp.at(1)?;
+        let topics = (0..topics_rlp.item_count()?)
+            .map(|i| fixed_at(&topics_rlp, i))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { address: fixed_at(rlp, 0)?, topics, data: rlp.val_at(2)? })
+    }
+}
+
+/// The outcome recorded in a receipt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReceiptOutcome {
+    /// Intermediate state root, used before Byzantium
+    StateRoot(Hash),
+    /// Success flag, used from Byzantium (EIP-658) onwards
+    Status(bool),
+}
+
+/// A transaction receipt as stored in the receipt trie
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Receipt {
+    /// EIP-2718 transaction type; 0 for legacy receipts
+    pub tx_type: u8,
+    /// Post-transaction state root or status
+    pub outcome: ReceiptOutcome,
+    /// Gas used in the block up to and including this transaction
+    pub cumulative_gas_used: u64,
+    /// 256-byte bloom filter over the receipt's logs
+    pub logs_bloom: Vec<u8>,
+    /// Logs emitted by the transaction
+    pub logs: Vec<Log>,
+}
+
+impl Receipt {
+    /// Returns the encoding stored in the receipt trie
+    ///
+    /// Legacy receipts are an RLP list; typed receipts are the envelope
+    /// `tx_type || rlp(list)`.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut stream = RlpStream::new_list(4);
+        match self.outcome {
+            ReceiptOutcome::StateRoot(root) => stream.append(&root.as_slice()),
+            ReceiptOutcome::Status(success) => stream.append(&u8::from(success)),
+        };
+        stream.append(&self.cumulative_gas_used);
+        stream.append(&self.logs_bloom);
+        stream.append_list(&self.logs);
+        let payload = stream.out();
+
+        if self.tx_type == 0 {
+            payload.to_vec()
+        } else {
+            [&[self.tx_type][..], &payload].concat()
+        }
+    }
+
+    /// Decodes a receipt from its receipt trie encoding
+    pub fn decode(data: &[u8]) -> Result<Self, MptError> {
+        let (tx_type, payload) = match data.first() {
+            // RLP lists start at 0xc0; typed envelopes start with the type byte
+            Some(&tx_type) if tx_type <= 0x7f => (tx_type, &data[1..]),
+            _ => (0, data),
+        };
+
+        // Reject trailing bytes, as `BlockHeader::decode` does
+        let rlp = Rlp::new(payload);
+        let info = rlp.payload_info()?;
+        if info.header_len + info.value_len != payload.len() {
+            return Err(DecoderError::RlpInconsistentLengthAndData.into());
+        }
+        if rlp.item_count()? != 4 {
+            return Err(DecoderError::RlpIncorrectListLen.into());
+        }
+        let outcome_bytes = rlp.at(0)?.data()?;
+        let outcome = match outcome_bytes {
+            [] => ReceiptOutcome::Status(false),
+            [1] => ReceiptOutcome::Status(true),
+            _ => ReceiptOutcome::StateRoot(fixed_at(&rlp, 0)?),
+        };
+        let logs_bloom: Vec<u8> = rlp.val_at(2)?;
+        if logs_bloom.len() != 256 {
+            return Err(DecoderError::Custom("Invalid logs bloom length").into());
+        }
+
+        Ok(Self {
+            tx_type,
+            outcome,
+            cumulative_gas_used: rlp.val_at(1)?,
+            logs_bloom,
+            logs: rlp.list_at(3)?,
+        })
+    }
+}
+
+/// Selects logs by emitting contract and topics
+///
+/// Topics are matched by position: `None` accepts any topic at that position,
+/// and a log must have at least as many topics as the filter lists.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogFilter {
+    /// Required emitting contract, if any
+    pub address: Option<Address>,
+    /// Required topics by position
+    pub topics: Vec<Option<Hash>>,
+}
+
+impl LogFilter {
+    /// Returns true if `log` satisfies the filter
+    pub fn matches(&self, log: &Log) -> bool {
+        if self.address.is_some_and(|address| address != log.address) {
+            return false;
+        }
+        self.topics.len() <= log.topics.len()
+            && self
+                .topics
+                .iter()
+                .zip(&log.topics)
+                .all(|(wanted, topic)| wanted.is_none_or(|wanted| wanted == *topic))
+    }
+}
+
+/// Everything a light client needs to check a receipt against a block hash
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReceiptProof {
+    /// RLP-encoded block header
+    pub header: Vec<u8>,
+    /// Position of the transaction in the block
+    pub tx_index: usize,
+    /// Proof of the receipt in the block's receipt trie
+    pub proof: MerkleProof,
+}
+
+impl ReceiptProof {
+    /// Verifies the proof against a trusted block hash and returns the receipt
+    ///
+    /// Checks that the header hashes to `block_hash`, that the receipt is
+    /// proven under the header's `receiptsRoot`, and that it decodes.
+    pub fn verify(&self, block_hash: Hash) -> Result<Receipt, InclusionError> {
+        let header = BlockHeader::decode(&self.header).map_err(InclusionError::InvalidHeader)?;
+        let actual = header.hash();
+        if actual != block_hash {
+            return Err(InclusionError::BlockHashMismatch { expected: block_hash, actual });
+        }
+
+        let encoded = verify_proof(header.receipts_root, &index_key(self.tx_index), &self.proof)?
+            .ok_or(InclusionError::ReceiptNotFound(self.tx_index))?;
+        Receipt::decode(&encoded).map_err(InclusionError::InvalidReceipt)
+    }
+
+    /// Verifies the proof and returns the receipt's logs matching `filter`
+    ///
+    /// Fails with `InclusionError::LogNotFound` if the proven receipt holds
+    /// no matching log.
+    pub fn verify_logs(&self, block_hash: Hash, filter: &LogFilter) -> Result<Vec<Log>, InclusionError> {
+        let receipt = self.verify(block_hash)?;
+        let logs: Vec<Log> = receipt.logs.into_iter().filter(|log| filter.matches(log)).collect();
+        if logs.is_empty() {
+            return Err(InclusionError::LogNotFound);
+        }
+        Ok(logs)
+    }
+}
+
+/// Errors that can occur while verifying a receipt or log inclusion
+#[derive(Debug, Clone, PartialEq)]
+pub enum InclusionError {
+    /// The header is not a valid RLP-encoded block header
+    InvalidHeader(MptError),
+    /// The header does not hash to the trusted block hash
+    BlockHashMismatch { expected: Hash, actual: Hash },
+    /// The receipt proof is incomplete or malformed
+    Proof(ProofError),
+    /// The proof shows that the block has no receipt at this index
+    ReceiptNotFound(usize),
+    /// The proven receipt cannot be decoded
+    InvalidReceipt(MptError),
+    /// The receipt holds no log matching the filter
+    LogNotFound,
+}
+
+impl fmt::Display for InclusionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InclusionError::InvalidHeader(err) => write!(f, "invalid block header: {}", err),
+            InclusionError::BlockHashMismatch { expected, actual } => write!(
+                f,
+                "header hashes to 0x{}, expected 0x{}",
+                hex::encode(actual),
+                hex::encode(expected)
+            ),
+            InclusionError::Proof(err) => write!(f, "invalid receipt proof: {}", err),
+            InclusionError::ReceiptNotFound(index) => write!(f, "no receipt at index {}", index),
+            InclusionError::InvalidReceipt(err) => write!(f, "invalid receipt: {}", err),
+            InclusionError::LogNotFound => write!(f, "no matching log in receipt"),
+        }
+    }
+}
+
+impl std::error::Error for InclusionError {}
+
+impl From<ProofError> for InclusionError {
+    fn from(err: ProofError) -> Self {
+        InclusionError::Proof(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::{EMPTY_ROOT, keccak256};
+    use crate::ordered::ReceiptTrie;
+
+    const TOKEN: Address = [0x70; 20];
+
+    fn transfer_log(from: u8, to: u8) -> Log {
+        Log {
+            address: TOKEN,
+            topics: vec![keccak256(b"Transfer(address,address,uint256)"), [from; 32], [to; 32]],
+            data: vec![0x03, 0xe8],
+        }
+    }
+
+    fn receipts() -> Vec<Receipt> {
+        (0..20u8)
+            .map(|i| Receipt {
+                // Mix legacy and EIP-1559 (type 2) receipts
+                tx_type: if i % 2 == 0 { 0 } else { 2 },
+                outcome: ReceiptOutcome::Status(i != 7),
+                cumulative_gas_used: 21_000 * (i as u64 + 1),
+                logs_bloom: vec![0; 256],
+                logs: if i == 5 { vec![transfer_log(0xaa, 0xbb)] } else { vec![] },
+            })
+            .collect()
+    }
+
+    /// Encodes a minimal header committing to `receipts_root`
+    fn header_rlp(receipts_root: Hash) -> Vec<u8> {
+        let mut stream = RlpStream::new_list(15);
+        for field in 0..15 {
+            match field {
+                5 => stream.append(&receipts_root.as_slice()),
+                0 | 1 | 3 | 4 | 13 => stream.append(&EMPTY_ROOT.as_slice()),
+                2 => stream.append(&[0u8; 20].as_slice()),
+                6 => stream.append(&vec![0u8; 256]),
+                12 => stream.append(&Vec::<u8>::new()),
+                14 => stream.append(&[0u8; 8].as_slice()),
+                _ => stream.append(&(field as u64)),
+            };
+        }
+        stream.out().to_vec()
+    }
+
+    /// Builds a block's receipt trie and a proof for receipt `tx_index`
+    fn receipt_proof(tx_index: usize) -> (Hash, ReceiptProof) {
+        let mut trie = ReceiptTrie::new();
+        for receipt in receipts() {
+            trie.push(receipt.encode()).unwrap();
+        }
+        let header = header_rlp(trie.root_hash());
+        let block_hash = keccak256(&header);
+        let proof = ReceiptProof { header, tx_index, proof: trie.prove(tx_index).unwrap() };
+        (block_hash, proof)
+    }
+
+    #[test]
+    fn test_receipt_roundtrip() {
+        for receipt in receipts() {
+            assert_eq!(Receipt::decode(&receipt.encode()), Ok(receipt));
+        }
+        let pre_byzantium = Receipt {
+            tx_type: 0,
+            outcome: ReceiptOutcome::StateRoot([0x55; 32]),
+            cumulative_gas_used: 1,
+            logs_bloom: vec![0; 256],
+            logs: vec![transfer_log(1, 2)],
+        };
+        assert_eq!(Receipt::decode(&pre_byzantium.encode()), Ok(pre_byzantium));
+        assert_eq!(receipts()[1].encode()[0], 0x02);
+    }
+
+    #[test]
+    fn test_trailing_bytes_rejected() {
+        for receipt in [&receipts()[0], &receipts()[1]] {
+            let encoded = receipt.encode();
+            assert!(Receipt::decode(&[encoded.as_slice(), &[0x00]].concat()).is_err());
+            assert!(Receipt::decode(&encoded[..encoded.len() - 1]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_verify_receipt_and_log() {
+        let (block_hash, proof) = receipt_proof(5);
+        assert_eq!(proof.verify(block_hash), Ok(receipts()[5].clone()));
+
+        let filter = LogFilter {
+            address: Some(TOKEN),
+            topics: vec![Some(keccak256(b"Transfer(address,address,uint256)")), None, Some([0xbb; 32])],
+        };
+        assert_eq!(proof.verify_logs(block_hash, &filter), Ok(vec![transfer_log(0xaa, 0xbb)]));
+
+        let wrong_recipient = LogFilter { topics: vec![None, None, Some([0xcc; 32])], ..filter.clone() };
+        assert_eq!(proof.verify_logs(block_hash, &wrong_recipient), Err(InclusionError::LogNotFound));
+        let wrong_contract = LogFilter { address: Some([0x71; 20]), ..filter };
+        assert_eq!(proof.verify_logs(block_hash, &wrong_contract), Err(InclusionError::LogNotFound));
+    }
+
+    #[test]
+    fn test_verify_typed_receipt() {
+        let (block_hash, proof) = receipt_proof(7);
+        let receipt = proof.verify(block_hash).unwrap();
+        assert_eq!(receipt.tx_type, 2);
+        assert_eq!(receipt.outcome, ReceiptOutcome::Status(false));
+    }
+
+    #[test]
+    fn test_rejects_untrusted_header() {
+        let (block_hash, proof) = receipt_proof(5);
+        let mut forged = block_hash;
+        forged[0] ^= 1;
+        assert!(matches!(proof.verify(forged), Err(InclusionError::BlockHashMismatch { .. })));
+
+        // A proof for one index does not prove another
+        let moved = ReceiptProof { tx_index: 4, ..proof.clone() };
+        assert!(matches!(moved.verify(block_hash), Err(InclusionError::Proof(_))));
+
+        // An index past the last receipt is proven absent
+        let (block_hash, proof) = receipt_proof(20);
+        assert_eq!(proof.verify(block_hash), Err(InclusionError::ReceiptNotFound(20)));
+    }
+}
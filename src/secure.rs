@@ -0,0 +1,252 @@
+use crate::db::{MemoryDb, NodeDb};
+use crate::error::MptError;
+use crate::iter::TrieIter;
+use crate::node::{Hash, TrieNode, EMPTY_ROOT, keccak256};
+use crate::multiproof::MultiProof;
+use crate::proof::MerkleProof;
+use crate::trie::MerklePatriciaTrie;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A trie that stores every entry under the Keccak-256 hash of its key
+///
+/// This is how Ethereum keys its state and storage tries. Hashing spreads keys
+/// evenly over the trie, so an attacker cannot craft keys that force long,
+/// expensive paths. The underlying trie only ever sees 32-byte hashed keys.
+///
+/// The original keys can optionally be kept in a preimage store, which lets
+/// iteration report them instead of their hashes.
+pub struct SecureTrie<D: NodeDb = MemoryDb> {
+    trie: MerklePatriciaTrie<D>,
+    /// Original keys indexed by their hash, when recording is enabled
+    preimages: Option<HashMap<Hash, Vec<u8>>>,
+}
+
+impl SecureTrie {
+    /// Creates a new empty secure trie
+    pub fn new() -> Self {
+        Self::with_db(MemoryDb::new(), EMPTY_ROOT)
+    }
+}
+
+impl<D: NodeDb> SecureTrie<D> {
+    /// Creates a secure trie over an existing node store, starting from `root`
+    pub fn with_db(db: D, root: Hash) -> Self {
+        Self::from_trie(MerklePatriciaTrie::with_db(db, root))
+    }
+
+    /// Wraps a trie whose keys are already hashed
+    pub fn from_trie(trie: MerklePatriciaTrie<D>) -> Self {
+        Self { trie, preimages: None }
+    }
+
+    /// Enables recording of key preimages from now on
+    ///
+    /// Keys written before recording was enabled remain known only by their
+    /// hash.
+    pub fn with_preimages(mut self) -> Self {
+        self.preimages.get_or_insert_with(HashMap::new);
+        self
+    }
+
+    /// Returns the underlying trie, keyed by hashed keys
+    pub fn trie(&self) -> &MerklePatriciaTrie<D> {
+        &self.trie
+    }
+
+    /// Consumes the wrapper and returns the underlying trie
+    pub fn into_trie(self) -> MerklePatriciaTrie<D> {
+        self.trie
+    }
+
+    /// Returns the root hash of the trie
+    pub fn root_hash(&self) -> Hash {
+        self.trie.root_hash()
+    }
+
+    /// Stores all uncommitted changes and returns the root hash
+    pub fn commit(&mut self) -> Result<Hash, MptError> {
+        self.trie.commit()
+    }
+
+    /// Returns the root hash as of the last commit
+    pub(crate) fn committed_root(&self) -> Hash {
+        self.trie.committed_root()
+    }
+
+    /// Returns the committed root and the uncommitted root node, if any
+    pub(crate) fn root_state(&self) -> (Hash, Option<Arc<TrieNode>>) {
+        self.trie.root_state()
+    }
+
+    /// Restores a state returned by `root_state`
+    pub(crate) fn restore_root(&mut self, state: (Hash, Option<Arc<TrieNode>>)) {
+        self.trie.restore_root(state);
+    }
+
+    /// Moves the trie to another root in the same node store, discarding
+    /// uncommitted changes
+    pub(crate) fn set_root_hash(&mut self, root: Hash) {
+        self.trie.set_root_hash(root);
+    }
+
+    /// Retrieves a value by key from the trie with root `root` in the same
+    /// node store
+    pub(crate) fn get_at_root(&self, root: Hash, key: &[u8]) -> Result<Option<Vec<u8>>, MptError> {
+        self.trie.get_at_root(root, &keccak256(key))
+    }
+
+    /// Generates a proof for a key in the trie with root `root` in the same
+    /// node store
+    pub(crate) fn prove_at_root(&self, root: Hash, key: &[u8]) -> Result<MerkleProof, MptError> {
+        self.trie.prove_at_root(root, &keccak256(key))
+    }
+
+    /// Returns the original key for a hashed key, if it was recorded
+    pub fn preimage(&self, hashed_key: &Hash) -> Option<&[u8]> {
+        self.preimages.as_ref()?.get(hashed_key).map(Vec::as_slice)
+    }
+
+    /// Inserts a key-value pair, keyed by `keccak256(key)`
+    pub fn insert(&mut self, key: &[u8], value: Vec<u8>) -> Result<(), MptError> {
+        let hashed_key = keccak256(key);
+        self.trie.insert(&hashed_key, value)?;
+        if let Some(preimages) = &mut self.preimages {
+            preimages.insert(hashed_key, key.to_vec());
+        }
+        Ok(())
+    }
+
+    /// Retrieves a value by key
+    pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, MptError> {
+        self.trie.get(&keccak256(key))
+    }
+
+    /// Deletes a key
+    ///
+    /// The key's preimage, if recorded, is kept: it may still be needed to
+    /// read older roots.
+    pub fn delete(&mut self, key: &[u8]) -> Result<(), MptError> {
+        self.trie.delete(&keccak256(key))
+    }
+
+    /// Generates a Merkle proof for a key
+    ///
+    /// The proof is for the hashed key, so verify it with
+    /// `verify_proof(root, &keccak256(key), &proof)`.
+    pub fn prove(&self, key: &[u8]) -> Result<MerkleProof, MptError> {
+        self.trie.prove(&keccak256(key))
+    }
+
+    /// Generates a single proof for all of `keys`
+    ///
+    /// As with `prove`, the proof is for the hashed keys, so verify it with
+    /// `verify_multiproof(root, &hashed_keys, &proof)`.
+    pub fn prove_many<K: AsRef<[u8]>>(&self, keys: &[K]) -> Result<MultiProof, MptError> {
+        let hashed: Vec<Hash> = keys.iter().map(|key| keccak256(key.as_ref())).collect();
+        self.trie.prove_many(&hashed)
+    }
+
+    /// Returns an iterator over all key/value pairs, in hashed key order
+    ///
+    /// Keys whose preimage was recorded are reported as the original key;
+    /// all others are reported as their 32-byte hash.
+    pub fn iter(&self) -> SecureTrieIter<'_, D> {
+        SecureTrieIter { inner: self.trie.iter(), preimages: self.preimages.as_ref() }
+    }
+}
+
+impl Default for SecureTrie {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Iterator over the entries of a `SecureTrie`
+pub struct SecureTrieIter<'a, D: NodeDb> {
+    inner: TrieIter<'a, D>,
+    preimages: Option<&'a HashMap<Hash, Vec<u8>>>,
+}
+
+impl<D: NodeDb> Iterator for SecureTrieIter<'_, D> {
+    type Item = Result<(Vec<u8>, Vec<u8>), MptError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.inner.next()?;
+        Some(item.map(|(hashed_key, value)| {
+            let preimage = Hash::try_from(hashed_key.as_slice())
+                .ok()
+                .and_then(|hash| self.preimages?.get(&hash));
+            match preimage {
+                Some(key) => (key.clone(), value),
+                None => (hashed_key, value),
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::multiproof::verify_multiproof;
+    use crate::proof::verify_proof;
+
+    #[test]
+    fn test_keys_are_hashed() {
+        let mut secure = SecureTrie::new();
+        let mut plain = MerklePatriciaTrie::new();
+        for (key, value) in [(&b"do"[..], &b"verb"[..]), (b"dog", b"puppy"), (b"horse", b"stallion")] {
+            secure.insert(key, value.to_vec()).unwrap();
+            plain.insert(&keccak256(key), value.to_vec()).unwrap();
+        }
+
+        assert_eq!(secure.root_hash(), plain.root_hash());
+        assert_eq!(secure.get(b"dog").unwrap(), Some(b"puppy".to_vec()));
+        assert_eq!(secure.trie().get(&keccak256(b"dog")).unwrap(), Some(b"puppy".to_vec()));
+        assert_eq!(secure.trie().get(b"dog").unwrap(), None);
+
+        secure.delete(b"dog").unwrap();
+        plain.delete(&keccak256(b"dog")).unwrap();
+        assert_eq!(secure.get(b"dog").unwrap(), None);
+        assert_eq!(secure.root_hash(), plain.root_hash());
+    }
+
+    #[test]
+    fn test_prove_hashed_key() {
+        let mut secure = SecureTrie::new();
+        secure.insert(b"dog", b"puppy".to_vec()).unwrap();
+        secure.insert(b"doge", b"coin".to_vec()).unwrap();
+
+        let root = secure.root_hash();
+        let proof = secure.prove(b"doge").unwrap();
+        assert_eq!(verify_proof(root, &keccak256(b"doge"), &proof), Ok(Some(b"coin".to_vec())));
+        let proof = secure.prove(b"cat").unwrap();
+        assert_eq!(verify_proof(root, &keccak256(b"cat"), &proof), Ok(None));
+
+        let proof = secure.prove_many(&[&b"dog"[..], b"cat"]).unwrap();
+        let hashed = [keccak256(b"dog"), keccak256(b"cat")];
+        assert_eq!(verify_multiproof(root, &hashed, &proof), Ok(vec![Some(b"puppy".to_vec()), None]));
+    }
+
+    #[test]
+    fn test_iter_with_and_without_preimages() {
+        let mut secure = SecureTrie::new();
+        secure.insert(b"early", b"1".to_vec()).unwrap();
+        let mut secure = secure.with_preimages();
+        secure.insert(b"dog", b"2".to_vec()).unwrap();
+        secure.insert(b"horse", b"3".to_vec()).unwrap();
+
+        assert_eq!(secure.preimage(&keccak256(b"dog")), Some(&b"dog"[..]));
+        assert_eq!(secure.preimage(&keccak256(b"early")), None);
+
+        let mut items: Vec<_> = secure.iter().collect::<Result<_, _>>().unwrap();
+        items.sort();
+        let mut expected = vec![
+            (keccak256(b"early").to_vec(), b"1".to_vec()),
+            (b"dog".to_vec(), b"2".to_vec()),
+            (b"horse".to_vec(), b"3".to_vec()),
+        ];
+        expected.sort();
+        assert_eq!(items, expected);
+    }
+}
@@ -0,0 +1,274 @@
+use crate::db::{MemoryDb, NodeDb};
+use crate::error::MptError;
+use crate::nibbles::{bytes_to_nibbles, common_prefix_len};
+use crate::node::{ChildRef, Hash, Node, EMPTY_ROOT, keccak256};
+
+/// Streaming builder computing a trie root from keys in increasing order
+///
+/// Produces the same root as inserting the same entries into a
+/// `MerklePatriciaTrie`, but never holds more than the path to the most
+/// recent key: every subtrie to its left is complete, so it is hashed as soon
+/// as the next key moves past it and only its reference is kept. Memory use
+/// therefore depends on the key length, not on the number of entries.
+///
+/// Finished nodes can be written to an optional sink, leaving a node store
+/// that a `MerklePatriciaTrie` can open at the returned root.
+pub struct StackTrie<D: NodeDb = MemoryDb> {
+    /// Receives every node that is referenced by hash
+    sink: Option<D>,
+    /// Branches on the path to the most recent key, shallowest first
+    stack: Vec<Frame>,
+    /// The most recent key (as nibbles) and its value, not yet placed
+    last: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+/// A branch under construction at nibble depth `depth`
+struct Frame {
+    depth: usize,
+    children: Box<[Option<ChildRef>; 16]>,
+    value: Option<Vec<u8>>,
+}
+
+impl StackTrie {
+    /// Creates a builder that only computes the root
+    pub fn new() -> Self {
+        Self { sink: None, stack: Vec::new(), last: None }
+    }
+}
+
+impl<D: NodeDb> StackTrie<D> {
+    /// Creates a builder that writes every finished node to `sink`
+    pub fn with_sink(sink: D) -> Self {
+        Self { sink: Some(sink), stack: Vec::new(), last: None }
+    }
+
+    /// Returns the node sink, if any
+    pub fn sink(&self) -> Option<&D> {
+        self.sink.as_ref()
+    }
+
+    /// Consumes the builder and returns its node sink, if any
+    pub fn into_sink(self) -> Option<D> {
+        self.sink
+    }
+
+    /// Adds an entry
+    ///
+    /// Fails with `UnsortedKey` unless `key` is strictly greater than the
+    /// previous key.
+    pub fn insert(&mut self, key: &[u8], value: Vec<u8>) -> Result<(), MptError> {
+        let nibbles = bytes_to_nibbles(key);
+        if let Some((last, last_value)) = self.last.take() {
+            if nibbles <= last {
+                self.last = Some((last, last_value));
+                return Err(MptError::UnsortedKey(key.to_vec()));
+            }
+            // The new key leaves the previous one at the first differing
+            // nibble; everything below that point on the old path is final
+            let depth = common_prefix_len(&last, &nibbles);
+            self.place_last(&last, last_value, depth)?;
+        }
+        self.last = Some((nibbles, value));
+        Ok(())
+    }
+
+    /// Finishes the trie and returns its root hash
+    ///
+    /// The builder is left empty and can be reused for another trie.
+    pub fn finish(&mut self) -> Result<Hash, MptError> {
+        let root = match (self.last.take(), self.stack.first()) {
+            (None, _) => return Ok(EMPTY_ROOT),
+            // A single entry is a lone leaf holding the whole key
+            (Some((last, value)), None) => Node::new_leaf(&last, value),
+            (Some((last, value)), Some(first)) => {
+                // Close every branch into the shallowest one, the root branch
+                let depth = first.depth;
+                self.place_last(&last, value, depth)?;
+                let frame = self.stack.pop().expect("the root branch remains");
+                let branch = Node::Branch(frame.children, frame.value);
+                if depth == 0 {
+                    branch
+                } else {
+                    let branch_ref = self.child_ref(branch)?;
+                    Node::new_extension(&last[..depth], branch_ref)
+                }
+            }
+        };
+
+        // The root is always referenced by hash, even when its encoding is
+        // shorter than 32 bytes
+        let encoded = root.encode_raw();
+        let hash = keccak256(&encoded);
+        if let Some(sink) = &mut self.sink {
+            sink.put(hash, encoded)?;
+        }
+        Ok(hash)
+    }
+
+    /// Helper: places the previous key `last` into the branch at nibble
+    /// `depth` on its path, then closes every deeper branch into its parent
+    ///
+    /// Leaves the branch at `depth` on top of the stack, creating it if the
+    /// path had no branch there yet.
+    fn place_last(&mut self, last: &[u8], value: Vec<u8>, depth: usize) -> Result<(), MptError> {
+        if self.stack.last().is_none_or(|top| top.depth < depth) {
+            self.stack.push(Frame::new(depth));
+        }
+
+        // The key ends in the deepest branch on its path
+        let top_depth = self.top().depth;
+        if top_depth == last.len() {
+            self.top().value = Some(value);
+        } else {
+            let leaf_ref = self.child_ref(Node::new_leaf(&last[top_depth + 1..], value))?;
+            self.top().children[last[top_depth] as usize] = Some(leaf_ref);
+        }
+
+        while self.top().depth > depth {
+            let frame = self.stack.pop().expect("checked above");
+            if self.stack.last().is_none_or(|parent| parent.depth < depth) {
+                self.stack.push(Frame::new(depth));
+            }
+            let parent_depth = self.top().depth;
+            let mut child = self.child_ref(Node::Branch(frame.children, frame.value))?;
+            if frame.depth > parent_depth + 1 {
+                // Nibbles between the two branches form an extension
+                let extension = Node::new_extension(&last[parent_depth + 1..frame.depth], child);
+                child = self.child_ref(extension)?;
+            }
+            self.top().children[last[parent_depth] as usize] = Some(child);
+        }
+        Ok(())
+    }
+
+    /// Helper: returns the deepest open branch
+    fn top(&mut self) -> &mut Frame {
+        self.stack.last_mut().expect("the stack holds at least one branch")
+    }
+
+    /// Helper: builds the reference a parent holds for a finished node,
+    /// writing it to the sink if it is referenced by hash
+    fn child_ref(&mut self, node: Node) -> Result<ChildRef, MptError> {
+        let encoded = node.encode_raw();
+        if encoded.len() < 32 {
+            return Ok(ChildRef::Inline(Box::new(node)));
+        }
+        let hash = keccak256(&encoded);
+        if let Some(sink) = &mut self.sink {
+            sink.put(hash, encoded)?;
+        }
+        Ok(ChildRef::Hash(hash))
+    }
+}
+
+impl Default for StackTrie {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Frame {
+    fn new(depth: usize) -> Self {
+        Self { depth, children: Box::default(), value: None }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trie::MerklePatriciaTrie;
+
+    /// Builds the root of `entries` with both a stack trie and a regular trie
+    fn roots(entries: &[(Vec<u8>, Vec<u8>)]) -> (Hash, Hash) {
+        let mut sorted = entries.to_vec();
+        sorted.sort();
+        let mut stack = StackTrie::new();
+        let mut trie = MerklePatriciaTrie::new();
+        for (key, value) in &sorted {
+            stack.insert(key, value.clone()).unwrap();
+            trie.insert(key, value.clone()).unwrap();
+        }
+        (stack.finish().unwrap(), trie.root_hash())
+    }
+
+    fn entry(key: &[u8], value: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        (key.to_vec(), value.to_vec())
+    }
+
+    #[test]
+    fn test_small_tries_match() {
+        assert_eq!(StackTrie::new().finish().unwrap(), EMPTY_ROOT);
+
+        let cases = [
+            vec![entry(b"dog", b"puppy")],
+            vec![entry(b"do", b"verb"), entry(b"dog", b"puppy"), entry(b"doge", b"coin"), entry(b"horse", b"stallion")],
+            // Long values force hashed children; a shared prefix forces a root extension
+            vec![entry(b"abc1", &[1; 40]), entry(b"abc2", &[2; 40]), entry(b"abd", &[3; 40])],
+            // A key that is a prefix of every other key becomes a branch value
+            vec![entry(b"a", b"x"), entry(b"ab", b"y"), entry(b"ac", &[9; 50]), entry(b"abcdef", b"z")],
+            vec![entry(b"", b"root value"), entry(b"\x01", b"one")],
+        ];
+        for entries in cases {
+            let (stack, trie) = roots(&entries);
+            assert_eq!(stack, trie, "{:?}", entries);
+        }
+    }
+
+    #[test]
+    fn test_large_sorted_input_matches() {
+        // Hashed 32-byte keys, as in a state snapshot
+        let entries: Vec<_> = (0..2000u32)
+            .map(|i| (keccak256(&i.to_be_bytes()).to_vec(), vec![i as u8; 1 + (i % 50) as usize]))
+            .collect();
+        let (stack, trie) = roots(&entries);
+        assert_eq!(stack, trie);
+
+        // Variable-length keys sharing long prefixes
+        let entries: Vec<_> = (0..1000u32)
+            .map(|i| (format!("key/{}/{}", i % 7, i).into_bytes(), i.to_be_bytes().to_vec()))
+            .collect();
+        let (stack, trie) = roots(&entries);
+        assert_eq!(stack, trie);
+    }
+
+    #[test]
+    fn test_sink_receives_committed_nodes() {
+        let mut entries: Vec<_> = (0..300u32)
+            .map(|i| (keccak256(&i.to_le_bytes())[..8].to_vec(), vec![i as u8; 33]))
+            .collect();
+        entries.sort();
+
+        let mut stack = StackTrie::with_sink(MemoryDb::new());
+        let mut trie = MerklePatriciaTrie::new();
+        for (key, value) in &entries {
+            stack.insert(key, value.clone()).unwrap();
+            trie.insert(key, value.clone()).unwrap();
+        }
+        let root = stack.finish().unwrap();
+        assert_eq!(root, trie.commit().unwrap());
+
+        // The sink holds exactly the nodes a committed trie stores
+        let sink = stack.into_sink().unwrap();
+        assert_eq!(sink.len(), trie.db().len());
+        let reopened = MerklePatriciaTrie::with_db(sink, root);
+        for (key, value) in &entries {
+            assert_eq!(reopened.get(key).unwrap().as_ref(), Some(value));
+        }
+    }
+
+    #[test]
+    fn test_unsorted_keys_rejected() {
+        let mut stack = StackTrie::new();
+        stack.insert(b"b", b"1".to_vec()).unwrap();
+        assert_eq!(stack.insert(b"a", b"2".to_vec()), Err(MptError::UnsortedKey(b"a".to_vec())));
+        assert_eq!(stack.insert(b"b", b"3".to_vec()), Err(MptError::UnsortedKey(b"b".to_vec())));
+
+        // Rejected keys leave the builder untouched
+        stack.insert(b"c", b"4".to_vec()).unwrap();
+        let (expected, _) = roots(&[entry(b"b", b"1"), entry(b"c", b"4")]);
+        assert_eq!(stack.finish().unwrap(), expected);
+
+        // A finished builder starts over empty
+        assert_eq!(stack.finish().unwrap(), EMPTY_ROOT);
+    }
+}
@@ -0,0 +1,375 @@
+use crate::account::Account;
+use crate::db::{MemoryDb, NodeDb};
+use crate::eip1186::{AccountProof, StorageProof};
+use crate::error::MptError;
+use crate::node::{Hash, EMPTY_ROOT};
+use crate::proof::MerkleProof;
+use crate::secure::SecureTrie;
+use primitive_types::U256;
+use std::collections::BTreeMap;
+
+/// A 20-byte Ethereum account address
+pub type Address = [u8; 20];
+
+/// The Ethereum world state: accounts keyed by address
+///
+/// Backed by a `SecureTrie`, so each account is stored under
+/// `keccak256(address)` as its RLP encoding, exactly as in Ethereum's state
+/// trie. The root hash is therefore the block header's `stateRoot`.
+///
+/// Each account also owns a storage trie, kept in the same node store and
+/// committed to by the account's `storage_root`. Storage writes are buffered
+/// until `commit`, which applies them and updates the affected accounts.
+pub struct StateTrie<D: NodeDb = MemoryDb> {
+    trie: SecureTrie<D>,
+    /// Storage writes not yet applied, by account and slot
+    pending_storage: BTreeMap<Address, BTreeMap<Hash, U256>>,
+}
+
+impl StateTrie {
+    /// Creates an empty state
+    pub fn new() -> Self {
+        Self::with_db(MemoryDb::new(), EMPTY_ROOT)
+    }
+}
+
+impl<D: NodeDb> StateTrie<D> {
+    /// Opens the state with root `root` in an existing node store
+    pub fn with_db(db: D, root: Hash) -> Self {
+        Self { trie: SecureTrie::with_db(db, root), pending_storage: BTreeMap::new() }
+    }
+
+    /// Returns the underlying secure trie
+    pub fn trie(&self) -> &SecureTrie<D> {
+        &self.trie
+    }
+
+    /// Returns the state root hash
+    pub fn root_hash(&self) -> Hash {
+        self.trie.root_hash()
+    }
+
+    /// Retrieves the account at `address`, if it exists
+    pub fn get_account(&self, address: &Address) -> Result<Option<Account>, MptError> {
+        match self.trie.get(address)? {
+            Some(encoded) => Ok(Some(Account::decode(&encoded)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Creates or replaces the account at `address`
+    pub fn set_account(&mut self, address: &Address, account: &Account) -> Result<(), MptError> {
+        self.trie.insert(address, account.encode())
+    }
+
+    /// Removes the account at `address`, discarding its uncommitted storage
+    /// writes
+    pub fn remove_account(&mut self, address: &Address) -> Result<(), MptError> {
+        self.pending_storage.remove(address);
+        self.trie.delete(address)
+    }
+
+    /// Reads a storage slot of the account at `address`
+    ///
+    /// Sees uncommitted writes. Unset slots (and slots of missing accounts)
+    /// read as zero.
+    pub fn get_storage(&self, address: &Address, slot: &Hash) -> Result<U256, MptError> {
+        if let Some(value) = self.pending_storage.get(address).and_then(|slots| slots.get(slot)) {
+            return Ok(*value);
+        }
+        let storage_root = match self.get_account(address)? {
+            Some(account) => account.storage_root,
+            None => return Ok(U256::zero()),
+        };
+        match self.trie.get_at_root(storage_root, slot)? {
+            Some(encoded) => Ok(rlp::decode(&encoded)?),
+            None => Ok(U256::zero()),
+        }
+    }
+
+    /// Writes a storage slot of the account at `address`
+    ///
+    /// Writing zero clears the slot. The write takes effect on the next
+    /// `commit`, which creates the account if it does not exist yet and ends
+    /// up with non-empty storage.
+    pub fn set_storage(&mut self, address: &Address, slot: Hash, value: U256) {
+        self.pending_storage.entry(*address).or_default().insert(slot, value);
+    }
+
+    /// Applies all buffered storage writes, stores every changed node and
+    /// returns the new state root
+    ///
+    /// Each touched storage trie is updated under its key
+    /// `keccak256(slot)`, holding the value RLP-encoded as a big-endian
+    /// integer without leading zeros, and the account's `storage_root` is set
+    /// to the resulting root. Writes that leave a missing account without
+    /// storage do not create it, so that no empty account enters the state
+    /// (EIP-161).
+    pub fn commit(&mut self) -> Result<Hash, MptError> {
+        let pending = std::mem::take(&mut self.pending_storage);
+        for (address, slots) in pending {
+            let account = self.get_account(&address)?;
+            let storage_root = account.as_ref().map_or(EMPTY_ROOT, |account| account.storage_root);
+            let storage_root = self.update_storage(storage_root, &slots)?;
+            match account {
+                Some(account) => self.set_account(&address, &Account { storage_root, ..account })?,
+                None if storage_root == EMPTY_ROOT => {}
+                None => self.set_account(&address, &Account { storage_root, ..Account::default() })?,
+            }
+        }
+        self.trie.commit()
+    }
+
+    /// Helper: applies slot writes to the storage trie with root
+    /// `storage_root`, commits it and returns its new root
+    ///
+    /// Storage tries share the state's node store, so the state trie is moved
+    /// to the storage root for the duration of the update. Its uncommitted
+    /// changes are set aside meanwhile rather than committed, so the state
+    /// trie is only hashed and stored once, by `commit`.
+    fn update_storage(&mut self, storage_root: Hash, slots: &BTreeMap<Hash, U256>) -> Result<Hash, MptError> {
+        let state = self.trie.root_state();
+        self.trie.set_root_hash(storage_root);
+        let result = slots
+            .iter()
+            .try_for_each(|(slot, value)| {
+                if value.is_zero() {
+                    self.trie.delete(slot)
+                } else {
+                    self.trie.insert(slot, rlp::encode(value).to_vec())
+                }
+            })
+            .and_then(|_| self.trie.commit());
+        self.trie.restore_root(state);
+        result
+    }
+
+    /// Generates a proof for the account at `address`
+    ///
+    /// Verify it with `verify_proof(root, &keccak256(address), &proof)`; the
+    /// proven value is the RLP-encoded account.
+    pub fn prove_account(&self, address: &Address) -> Result<MerkleProof, MptError> {
+        self.trie.prove(address)
+    }
+
+    /// Generates an EIP-1186 (`eth_getProof`) proof for the account at
+    /// `address` and the given storage slots
+    ///
+    /// Proves the state as of the last commit; uncommitted account changes
+    /// and storage writes are not included. A missing account is reported as
+    /// clients report it, with zero values and code hash, an empty storage
+    /// root and an exclusion proof.
+    pub fn get_proof(&self, address: &Address, slots: &[Hash]) -> Result<AccountProof, MptError> {
+        let state_root = self.trie.committed_root();
+        let account = match self.trie.get_at_root(state_root, address)? {
+            Some(encoded) => Some(Account::decode(&encoded)?),
+            None => None,
+        };
+        let storage_root = account.as_ref().map_or(EMPTY_ROOT, |account| account.storage_root);
+        let storage_proof = slots
+            .iter()
+            .map(|slot| {
+                let value = match self.trie.get_at_root(storage_root, slot)? {
+                    Some(encoded) => rlp::decode(&encoded)?,
+                    None => U256::zero(),
+                };
+                let proof = self.trie.prove_at_root(storage_root, slot)?;
+                Ok(StorageProof { key: (*slot).into(), value, proof: proof.nodes })
+            })
+            .collect::<Result<_, MptError>>()?;
+
+        let account_proof = self.trie.prove_at_root(state_root, address)?.nodes;
+        Ok(match account {
+            Some(account) => AccountProof {
+                address: *address,
+                account_proof,
+                balance: account.balance,
+                code_hash: account.code_hash,
+                nonce: account.nonce,
+                storage_hash: account.storage_root,
+                storage_proof,
+            },
+            None => AccountProof {
+                address: *address,
+                account_proof,
+                balance: U256::zero(),
+                code_hash: [0; 32],
+                nonce: 0,
+                storage_hash: EMPTY_ROOT,
+                storage_proof,
+            },
+        })
+    }
+}
+
+impl Default for StateTrie {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::EMPTY_CODE_HASH;
+    use crate::node::keccak256;
+    use crate::proof::verify_proof;
+    use crate::test_utils::slot;
+    use crate::trie::MerklePatriciaTrie;
+
+    const ALICE: Address = [0xaa; 20];
+    const BOB: Address = [0xbb; 20];
+
+    #[test]
+    fn test_set_and_get_account() {
+        let mut state = StateTrie::new();
+        assert_eq!(state.root_hash(), EMPTY_ROOT);
+        assert_eq!(state.get_account(&ALICE).unwrap(), None);
+
+        let alice = Account::new(5, U256::from(1000));
+        state.set_account(&ALICE, &alice).unwrap();
+        state.set_account(&BOB, &Account::default()).unwrap();
+
+        let stored = state.get_account(&ALICE).unwrap().unwrap();
+        assert_eq!(stored, alice);
+        assert_eq!(stored.storage_root, EMPTY_ROOT);
+        assert_eq!(stored.code_hash, EMPTY_CODE_HASH);
+        assert!(state.get_account(&BOB).unwrap().unwrap().is_empty());
+
+        // Same layout as a secure trie of RLP-encoded accounts
+        let mut raw = SecureTrie::new();
+        raw.insert(&ALICE, alice.encode()).unwrap();
+        raw.insert(&BOB, Account::default().encode()).unwrap();
+        assert_eq!(state.root_hash(), raw.root_hash());
+
+        state.remove_account(&BOB).unwrap();
+        assert_eq!(state.get_account(&BOB).unwrap(), None);
+    }
+
+    #[test]
+    fn test_transfer_changes_root() {
+        let mut state = StateTrie::new();
+        state.set_account(&ALICE, &Account::new(0, U256::from(100))).unwrap();
+        state.set_account(&BOB, &Account::new(0, U256::zero())).unwrap();
+        let before = state.root_hash();
+
+        let mut alice = state.get_account(&ALICE).unwrap().unwrap();
+        let mut bob = state.get_account(&BOB).unwrap().unwrap();
+        alice.balance -= U256::from(10);
+        alice.nonce += 1;
+        bob.balance += U256::from(10);
+        state.set_account(&ALICE, &alice).unwrap();
+        state.set_account(&BOB, &bob).unwrap();
+
+        assert_ne!(state.root_hash(), before);
+        assert_eq!(state.get_account(&BOB).unwrap().unwrap().balance, U256::from(10));
+    }
+
+    #[test]
+    fn test_prove_account() {
+        let mut state = StateTrie::new();
+        let alice = Account::new(1, U256::from(7));
+        state.set_account(&ALICE, &alice).unwrap();
+        state.set_account(&BOB, &Account::new(2, U256::from(9))).unwrap();
+
+        let root = state.root_hash();
+        let proof = state.prove_account(&ALICE).unwrap();
+        let value = verify_proof(root, &keccak256(&ALICE), &proof).unwrap().unwrap();
+        assert_eq!(Account::decode(&value).unwrap(), alice);
+    }
+
+    #[test]
+    fn test_storage_commit_updates_storage_root() {
+        let mut state = StateTrie::new();
+        state.set_account(&ALICE, &Account::new(1, U256::from(50))).unwrap();
+        state.set_storage(&ALICE, slot(0), U256::from(0x1234));
+        state.set_storage(&ALICE, slot(1), U256::from(1) << 200);
+
+        // Pending writes are visible but not yet committed
+        assert_eq!(state.get_storage(&ALICE, &slot(0)).unwrap(), U256::from(0x1234));
+        assert_eq!(state.get_account(&ALICE).unwrap().unwrap().storage_root, EMPTY_ROOT);
+
+        let root = state.commit().unwrap();
+        assert_eq!(root, state.root_hash());
+
+        // The storage trie holds trimmed big-endian values under hashed slots
+        let mut expected = SecureTrie::new();
+        expected.insert(&slot(0), vec![0x82, 0x12, 0x34]).unwrap();
+        expected.insert(&slot(1), rlp::encode(&(U256::from(1) << 200)).to_vec()).unwrap();
+        let alice = state.get_account(&ALICE).unwrap().unwrap();
+        assert_eq!(alice.storage_root, expected.root_hash());
+        assert_eq!(alice.balance, U256::from(50));
+        assert_eq!(state.get_storage(&ALICE, &slot(1)).unwrap(), U256::from(1) << 200);
+        assert_eq!(state.get_storage(&ALICE, &slot(2)).unwrap(), U256::zero());
+    }
+
+    #[test]
+    fn test_zero_write_deletes_slot() {
+        let mut state = StateTrie::new();
+        state.set_storage(&BOB, slot(7), U256::from(9));
+        state.commit().unwrap();
+        assert_ne!(state.get_account(&BOB).unwrap().unwrap().storage_root, EMPTY_ROOT);
+
+        state.set_storage(&BOB, slot(7), U256::zero());
+        state.commit().unwrap();
+        assert_eq!(state.get_account(&BOB).unwrap().unwrap().storage_root, EMPTY_ROOT);
+        assert_eq!(state.get_storage(&BOB, &slot(7)).unwrap(), U256::zero());
+    }
+
+    #[test]
+    fn test_zero_writes_do_not_create_accounts() {
+        let mut state = StateTrie::new();
+        state.set_storage(&ALICE, slot(1), U256::zero());
+        assert_eq!(state.commit().unwrap(), EMPTY_ROOT);
+        assert_eq!(state.get_account(&ALICE).unwrap(), None);
+
+        // An existing account is kept, with its storage emptied
+        state.set_account(&BOB, &Account::new(1, U256::from(2))).unwrap();
+        state.set_storage(&BOB, slot(1), U256::from(3));
+        state.commit().unwrap();
+        state.set_storage(&BOB, slot(1), U256::zero());
+        state.commit().unwrap();
+        assert_eq!(state.get_account(&BOB).unwrap(), Some(Account::new(1, U256::from(2))));
+    }
+
+    #[test]
+    fn test_commit_stores_state_once() {
+        let mut state = StateTrie::new();
+        for n in 0..20u8 {
+            state.set_account(&[n; 20], &Account::new(n as u64, U256::from(n))).unwrap();
+            state.set_storage(&[n; 20], slot(n), U256::from(n as u64 + 1));
+        }
+        state.commit().unwrap();
+
+        // No intermediate state root was written along the way
+        let mut trie = MerklePatriciaTrie::with_db(state.trie().trie().db().clone(), state.root_hash());
+        let storage_roots: Vec<Hash> = (0..20u8)
+            .map(|n| state.get_account(&[n; 20]).unwrap().unwrap().storage_root)
+            .collect();
+        assert_eq!(trie.prune(&storage_roots).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_state_root_is_reproducible() {
+        let build = |order: &[(Address, u8, u64)]| {
+            let mut state = StateTrie::new();
+            for &(address, n, value) in order {
+                state.set_storage(&address, slot(n), U256::from(value));
+                state.commit().unwrap();
+            }
+            state.root_hash()
+        };
+
+        let writes = [(ALICE, 0, 1), (BOB, 3, 4), (ALICE, 2, 5), (BOB, 1, 6)];
+        let mut reversed = writes;
+        reversed.reverse();
+        assert_eq!(build(&writes), build(&reversed));
+
+        // Batching all writes into one commit gives the same root
+        let mut state = StateTrie::new();
+        for &(address, n, value) in &writes {
+            state.set_storage(&address, slot(n), U256::from(value));
+        }
+        assert_eq!(state.commit().unwrap(), build(&writes));
+    }
+}
@@ -0,0 +1,22 @@
+//! Helpers shared by the unit tests
+
+use crate::node::Hash;
+
+/// Minimal xorshift generator so randomized tests are reproducible
+pub(crate) struct XorShift(pub(crate) u64);
+
+impl XorShift {
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// Returns the storage slot key for slot number `n`
+pub(crate) fn slot(n: u8) -> Hash {
+    let mut slot = [0u8; 32];
+    slot[31] = n;
+    slot
+}
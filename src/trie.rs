@@ -1,72 +1,372 @@
-use crate::node::{Node, Hash, keccak256};
+use crate::node::{Node, Hash, ChildRef, Link, TrieNode, EMPTY_ROOT, keccak256};
 use crate::nibbles::{bytes_to_nibbles, compact_decode, common_prefix_len};
-use std::collections::HashMap;
+use crate::error::MptError;
+use crate::proof::MerkleProof;
+use crate::iter::{TrieIter, TrieCursor, TrieRange};
+use crate::db::{NodeDb, MemoryDb, FileDb};
+use crate::checkpoint::Checkpoint;
+use crate::version::Retention;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::RangeBounds;
+use std::path::Path;
+use std::sync::Arc;
 
 /// The Merkle Patricia Trie structure
-pub struct MerklePatriciaTrie {
-    /// Storage for nodes, indexed by their hash
-    storage: HashMap<Hash, Node>,
-    /// The root hash of the trie
-    root: Hash,
+///
+/// Generic over the node storage backend; the in-memory `MemoryDb` is used
+/// by default.
+///
+/// Inserts and deletes only rebuild the affected nodes in memory. Encoding,
+/// hashing and storing them is deferred to `commit`, so a node rewritten by
+/// many consecutive writes is hashed once.
+pub struct MerklePatriciaTrie<D: NodeDb = MemoryDb> {
+    /// Storage for encoded nodes, indexed by their hash
+    ///
+    /// Only nodes whose encoding is at least 32 bytes (and the root) are
+    /// stored here; smaller nodes are inlined into their parent.
+    pub(crate) storage: D,
+    /// The root hash as of the last commit
+    pub(crate) root: Hash,
+    /// The root node, if the trie changed since the last commit
+    pub(crate) dirty_root: Option<Arc<TrieNode>>,
+    /// Labelled roots recorded by `commit_version`, oldest first
+    pub(crate) versions: VecDeque<(u64, Hash)>,
+    /// How many labelled roots are kept readable
+    pub(crate) retention: Retention,
+    /// Roots kept readable through retention pruning by `retain_root`
+    pub(crate) retained: Vec<Hash>,
+    /// Reference counts of the stored nodes, tracked while retention is
+    /// limited
+    pub(crate) refs: Option<HashMap<Hash, usize>>,
+    /// Open checkpoints, oldest first
+    pub(crate) checkpoints: Vec<Checkpoint>,
+    /// Identifier for the next checkpoint
+    pub(crate) next_checkpoint: u64,
 }
 
 impl MerklePatriciaTrie {
     /// Creates a new empty trie
     pub fn new() -> Self {
-        let empty_hash = keccak256(&[]);
+        Self::with_db(MemoryDb::new(), EMPTY_ROOT)
+    }
+}
+
+impl MerklePatriciaTrie<FileDb> {
+    /// Opens a trie persisted in the file store at `path`, resuming at `root`
+    ///
+    /// Pass `EMPTY_ROOT` to start a new trie. Fails with `MissingNode` if the
+    /// store holds no node for a non-empty `root`.
+    pub fn open<P: AsRef<Path>>(path: P, root: Hash) -> Result<Self, MptError> {
+        let db = FileDb::open(path)?;
+        if root != EMPTY_ROOT && !db.contains(&root)? {
+            return Err(MptError::MissingNode(root));
+        }
+        Ok(Self::with_db(db, root))
+    }
+}
+
+impl<D: NodeDb> MerklePatriciaTrie<D> {
+    /// Creates a trie over an existing node store, starting from `root`
+    ///
+    /// Pass `EMPTY_ROOT` to start an empty trie on a fresh store.
+    pub fn with_db(db: D, root: Hash) -> Self {
         Self {
-            storage: HashMap::new(),
-            root: empty_hash,
+            storage: db,
+            root,
+            dirty_root: None,
+            versions: VecDeque::new(),
+            retention: Retention::All,
+            retained: Vec::new(),
+            refs: None,
+            checkpoints: Vec::new(),
+            next_checkpoint: 0,
         }
     }
     
+    /// Returns the underlying node store
+    pub fn db(&self) -> &D {
+        &self.storage
+    }
+    
+    /// Consumes the trie and returns its node store
+    ///
+    /// Uncommitted changes are not part of the store and are discarded.
+    pub fn into_db(self) -> D {
+        self.storage
+    }
+    
     /// Returns the root hash of the trie
+    ///
+    /// Covers uncommitted changes by hashing the modified nodes, without
+    /// storing them.
     pub fn root_hash(&self) -> Hash {
+        match &self.dirty_root {
+            Some(node) => node.hash(),
+            None => self.root,
+        }
+    }
+    
+    /// Returns true if the trie has changes that are not yet committed
+    pub fn is_dirty(&self) -> bool {
+        self.dirty_root.is_some()
+    }
+    
+    /// Returns the root hash as of the last commit
+    pub(crate) fn committed_root(&self) -> Hash {
         self.root
     }
     
+    /// Returns the committed root and the uncommitted root node, if any, so
+    /// that `restore_root` can bring them back after the trie moves elsewhere
+    pub(crate) fn root_state(&self) -> (Hash, Option<Arc<TrieNode>>) {
+        (self.root, self.dirty_root.clone())
+    }
+    
+    /// Restores a state returned by `root_state`
+    pub(crate) fn restore_root(&mut self, (root, dirty_root): (Hash, Option<Arc<TrieNode>>)) {
+        self.root = root;
+        self.dirty_root = dirty_root;
+    }
+    
+    /// Hashes and stores every node changed since the last commit and returns
+    /// the root hash
+    ///
+    /// Until committed, changes live only in memory: they are visible to
+    /// reads, iteration and proofs of this trie, but not in its node store.
+    pub fn commit(&mut self) -> Result<Hash, MptError> {
+        if let Some(node) = self.dirty_root.clone() {
+            self.root = match Arc::unwrap_or_clone(node) {
+                // The empty root is known without storing anything
+                TrieNode::Empty => EMPTY_ROOT,
+                node => {
+                    let node = self.commit_children(node)?;
+                    // The root is always referenced by hash, even when its
+                    // encoding is shorter than 32 bytes
+                    let encoded = node.encode_raw();
+                    let hash = keccak256(&encoded);
+                    self.track_new_node(hash, &node)?;
+                    self.storage.put(hash, encoded)?;
+                    hash
+                }
+            };
+            self.dirty_root = None;
+        }
+        Ok(self.root)
+    }
+    
+    /// Moves the trie to another root in the same node store, discarding
+    /// uncommitted changes
+    pub(crate) fn set_root_hash(&mut self, root: Hash) {
+        self.root = root;
+        self.dirty_root = None;
+    }
+    
+    /// Retrieves a value by key as of an earlier root in the same node store
+    ///
+    /// Any committed root whose nodes have not been pruned can be read, such
+    /// as one recorded by `commit_version`.
+    pub fn get_at_root(&self, root: Hash, key: &[u8]) -> Result<Option<Vec<u8>>, MptError> {
+        let nibbles = bytes_to_nibbles(key);
+        self.get_at(&nibbles, self.get_node(root)?.into())
+    }
+    
+    /// Generates a Merkle proof for a key in the trie with root `root` in the
+    /// same node store
+    pub(crate) fn prove_at_root(&self, root: Hash, key: &[u8]) -> Result<MerkleProof, MptError> {
+        self.prove_from(&Link::stored(root), key)
+    }
+    
     /// Inserts a key-value pair into the trie
-    pub fn insert(&mut self, key: &[u8], value: Vec<u8>) {
+    pub fn insert(&mut self, key: &[u8], value: Vec<u8>) -> Result<(), MptError> {
         let nibbles = bytes_to_nibbles(key);
-        self.root = self.insert_at(&nibbles, value, self.root);
+        let root = self.root_node()?;
+        let new_root = self.insert_at(&nibbles, value, root)?;
+        self.set_root(new_root);
+        Ok(())
     }
     
     /// Retrieves a value by key from the trie
-    pub fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+    ///
+    /// Returns `Ok(None)` only when the key is absent; a node missing from
+    /// storage is reported as `MptError::MissingNode`.
+    pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, MptError> {
+        let nibbles = bytes_to_nibbles(key);
+        self.get_at(&nibbles, self.root_node()?)
+    }
+    
+    /// Generates a Merkle proof for a key
+    ///
+    /// For a present key the proof ends at the node holding its value. For an
+    /// absent key it ends at the node where the path diverges (an empty branch
+    /// slot or a mismatched extension/leaf path), proving non-membership.
+    ///
+    /// Proving uncommitted changes hashes the modified nodes on the fly;
+    /// commit first when generating many proofs.
+    pub fn prove(&self, key: &[u8]) -> Result<MerkleProof, MptError> {
+        self.prove_from(&self.root_ref(), key)
+    }
+    
+    /// Helper: generates a Merkle proof for a key below the root `root`
+    fn prove_from(&self, root: &Link, key: &[u8]) -> Result<MerkleProof, MptError> {
         let nibbles = bytes_to_nibbles(key);
-        self.get_at(&nibbles, self.root)
+        let mut nodes = Vec::new();
+        self.prove_at(&nibbles, root, &mut nodes)?;
+        Ok(MerkleProof::new(nodes))
     }
     
     /// Deletes a key from the trie
-    pub fn delete(&mut self, key: &[u8]) {
+    pub fn delete(&mut self, key: &[u8]) -> Result<(), MptError> {
         let nibbles = bytes_to_nibbles(key);
-        self.root = self.delete_at(&nibbles, self.root);
+        let root = self.root_node()?;
+        if let Some(new_root) = self.delete_at(&nibbles, root)? {
+            self.set_root(new_root);
+        }
+        Ok(())
     }
     
-    /// Helper: inserts a value at a specific node
-    fn insert_at(&mut self, path: &[u8], value: Vec<u8>, node_hash: Hash) -> Hash {
-        // Get the node or treat as empty
-        let node = self.get_node(node_hash).unwrap_or(Node::Empty);
+    /// Returns an iterator over all key/value pairs in lexicographic key order
+    pub fn iter(&self) -> TrieIter<'_, D> {
+        TrieIter::new(self, Vec::new(), self.root_ref())
+    }
+    
+    /// Returns an iterator over the key/value pairs whose key starts with
+    /// `prefix`, in lexicographic key order
+    ///
+    /// Descends directly to the node covering the prefix instead of scanning
+    /// the whole trie.
+    pub fn iter_prefix(&self, prefix: &[u8]) -> Result<TrieIter<'_, D>, MptError> {
+        let nibbles = bytes_to_nibbles(prefix);
+        let mut remaining = nibbles.as_slice();
+        let mut child = self.root_ref();
         
-        match node {
-            Node::Empty => {
+        loop {
+            let consumed = nibbles[..nibbles.len() - remaining.len()].to_vec();
+            if remaining.is_empty() {
+                // The prefix is exhausted: everything below matches
+                return Ok(TrieIter::new(self, consumed, child));
+            }
+            
+            match self.resolve(&child)? {
+                TrieNode::Leaf(encoded_path, _) => {
+                    let (leaf_path, _) = compact_decode(&encoded_path)?;
+                    if leaf_path.starts_with(remaining) {
+                        return Ok(TrieIter::new(self, consumed, child));
+                    }
+                    break;
+                }
+                TrieNode::Extension(encoded_path, ext_child) => {
+                    let (ext_path, _) = compact_decode(&encoded_path)?;
+                    if ext_path.starts_with(remaining) {
+                        return Ok(TrieIter::new(self, consumed, child));
+                    }
+                    if !remaining.starts_with(&ext_path) {
+                        break;
+                    }
+                    remaining = &remaining[ext_path.len()..];
+                    child = ext_child;
+                }
+                TrieNode::Branch(mut children, _) => {
+                    match children[remaining[0] as usize].take() {
+                        Some(branch_child) => {
+                            remaining = &remaining[1..];
+                            child = branch_child;
+                        }
+                        None => break,
+                    }
+                }
+                TrieNode::Empty => break,
+            }
+        }
+        Ok(TrieIter::new(self, Vec::new(), Link::stored(EMPTY_ROOT)))
+    }
+    
+    /// Returns an iterator over the key/value pairs whose key falls within
+    /// `range`, in lexicographic key order
+    pub fn range<K: AsRef<[u8]>, R: RangeBounds<K>>(&self, range: R) -> TrieRange<'_, D> {
+        TrieRange::new(self, range)
+    }
+    
+    /// Returns an unpositioned cursor over the trie's keys
+    pub fn cursor(&self) -> TrieCursor<'_, D> {
+        TrieCursor::new(self)
+    }
+    
+    /// Removes every stored node that is unreachable from the current root,
+    /// the recorded versions, open checkpoints, the roots kept with
+    /// `retain_root` and `retained_roots`
+    ///
+    /// Updates and deletes leave superseded nodes behind in storage; this
+    /// mark-and-sweep pass reclaims them. Roots that are not retained can no
+    /// longer be read afterwards. The last committed root is kept alongside
+    /// any uncommitted changes. Returns the number of nodes removed.
+    pub fn prune(&mut self, retained_roots: &[Hash]) -> Result<usize, MptError> {
+        let mut live = HashSet::new();
+        self.mark_reachable(&self.root_ref(), &mut live)?;
+        for checkpoint in &self.checkpoints {
+            self.mark_reachable(&Link::stored(checkpoint.root), &mut live)?;
+            if let Some(node) = &checkpoint.dirty_root {
+                self.mark_reachable(&Link::Dirty(node.clone()), &mut live)?;
+            }
+        }
+        let versions = self.versions.iter().map(|(_, root)| root);
+        let kept = std::iter::once(&self.root).chain(versions).chain(&self.retained);
+        for root in kept.chain(retained_roots) {
+            self.mark_reachable(&Link::stored(*root), &mut live)?;
+        }
+        
+        let mut removed = 0;
+        for hash in self.storage.hashes()? {
+            if !live.contains(&hash) {
+                self.storage.remove(&hash)?;
+                removed += 1;
+            }
+        }
+        if self.refs.is_some() {
+            self.refs = Some(self.count_refs()?);
+        }
+        Ok(removed)
+    }
+    
+    /// Helper: records the hashes of all stored nodes reachable from `child`
+    fn mark_reachable(&self, child: &Link, live: &mut HashSet<Hash>) -> Result<(), MptError> {
+        if let Link::Stored(ChildRef::Hash(hash)) = child
+            && !live.insert(*hash)
+        {
+            // Already visited through a shared subtree
+            return Ok(());
+        }
+        
+        match self.resolve(child)? {
+            TrieNode::Extension(_, grandchild) => self.mark_reachable(&grandchild, live)?,
+            TrieNode::Branch(children, _) => {
+                for grandchild in children.iter().flatten() {
+                    self.mark_reachable(grandchild, live)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+    
+    /// Helper: inserts a value below `node` and returns the updated node
+    pub(crate) fn insert_at(&self, path: &[u8], value: Vec<u8>, node: TrieNode) -> Result<TrieNode, MptError> {
+        Ok(match node {
+            TrieNode::Empty => {
                 // Create a new leaf node
-                let leaf = Node::new_leaf(path, value);
-                self.store_node(leaf)
+                TrieNode::new_leaf(path, value)
             }
             
-            Node::Leaf(encoded_path, old_value) => {
-                let (leaf_path, _) = compact_decode(&encoded_path);
+            TrieNode::Leaf(encoded_path, old_value) => {
+                let (leaf_path, _) = compact_decode(&encoded_path)?;
                 
                 if leaf_path == path {
                     // Same key, update value
-                    let leaf = Node::new_leaf(path, value);
-                    self.store_node(leaf)
+                    TrieNode::new_leaf(path, value)
                 } else {
                     // Split the leaf into a branch
                     let common_len = common_prefix_len(&leaf_path, path);
                     
-                    let new_branch_hash = self.create_branch_from_divergence(
+                    let new_branch = Self::create_branch_from_divergence(
                         &leaf_path[common_len..],
                         old_value,
                         &path[common_len..],
@@ -75,309 +375,407 @@ impl MerklePatriciaTrie {
                     
                     if common_len > 0 {
                         // Create an extension node
-                        let ext = Node::new_extension(&path[..common_len], new_branch_hash);
-                        self.store_node(ext)
+                        let branch_ref = dirty(new_branch);
+                        TrieNode::new_extension(&path[..common_len], branch_ref)
                     } else {
-                        new_branch_hash
+                        new_branch
                     }
                 }
             }
             
-            Node::Extension(encoded_path, child_hash) => {
-                let (ext_path, _) = compact_decode(&encoded_path);
+            TrieNode::Extension(encoded_path, child) => {
+                let (ext_path, _) = compact_decode(&encoded_path)?;
                 let common_len = common_prefix_len(&ext_path, path);
                 
                 if common_len == ext_path.len() {
                     // Continue down the extension
-                    let new_child = self.insert_at(&path[common_len..], value, child_hash);
-                    let ext = Node::new_extension(&ext_path, new_child);
-                    self.store_node(ext)
+                    let child_node = self.resolve(&child)?;
+                    let new_child = self.insert_at(&path[common_len..], value, child_node)?;
+                    let child_ref = dirty(new_child);
+                    TrieNode::new_extension(&ext_path, child_ref)
                 } else {
                     // Split the extension
                     // Need to handle the old child properly
                     let remaining_ext_path = &ext_path[common_len..];
-                    let mut branch = Node::new_branch();
+                    let mut branch = TrieNode::new_branch();
                     
                     if remaining_ext_path.len() == 1 {
                         // Direct child
-                        if let Node::Branch(ref mut children, _) = branch {
-                            children[remaining_ext_path[0] as usize] = Some(child_hash);
+                        if let TrieNode::Branch(ref mut children, _) = branch {
+                            children[remaining_ext_path[0] as usize] = Some(child);
                         }
                     } else {
                         // Need extension
-                        let ext = Node::new_extension(&remaining_ext_path[1..], child_hash);
-                        let ext_hash = self.store_node(ext);
-                        if let Node::Branch(ref mut children, _) = branch {
-                            children[remaining_ext_path[0] as usize] = Some(ext_hash);
+                        let ext = TrieNode::new_extension(&remaining_ext_path[1..], child);
+                        let ext_ref = dirty(ext);
+                        if let TrieNode::Branch(ref mut children, _) = branch {
+                            children[remaining_ext_path[0] as usize] = Some(ext_ref);
                         }
                     }
                     
                     // Insert new value
                     let remaining_new_path = &path[common_len..];
                     if remaining_new_path.is_empty() {
-                        if let Node::Branch(_, ref mut branch_value) = branch {
+                        if let TrieNode::Branch(_, ref mut branch_value) = branch {
                             *branch_value = Some(value);
                         }
-                    } else if remaining_new_path.len() == 1 {
-                        let leaf = Node::new_leaf(&[], value);
-                        let leaf_hash = self.store_node(leaf);
-                        if let Node::Branch(ref mut children, _) = branch {
-                            children[remaining_new_path[0] as usize] = Some(leaf_hash);
-                        }
                     } else {
-                        let leaf = Node::new_leaf(&remaining_new_path[1..], value);
-                        let leaf_hash = self.store_node(leaf);
-                        if let Node::Branch(ref mut children, _) = branch {
-                            children[remaining_new_path[0] as usize] = Some(leaf_hash);
+                        let leaf = TrieNode::new_leaf(&remaining_new_path[1..], value);
+                        let leaf_ref = dirty(leaf);
+                        if let TrieNode::Branch(ref mut children, _) = branch {
+                            children[remaining_new_path[0] as usize] = Some(leaf_ref);
                         }
                     }
                     
-                    let branch_hash = self.store_node(branch);
-                    
                     if common_len > 0 {
-                        let ext = Node::new_extension(&path[..common_len], branch_hash);
-                        self.store_node(ext)
+                        let branch_ref = dirty(branch);
+                        TrieNode::new_extension(&path[..common_len], branch_ref)
                     } else {
-                        branch_hash
+                        branch
                     }
                 }
             }
             
-            Node::Branch(mut children, mut branch_value) => {
+            TrieNode::Branch(mut children, mut branch_value) => {
                 if path.is_empty() {
                     // Insert value at this branch
                     branch_value = Some(value);
-                    let branch = Node::Branch(children, branch_value);
-                    self.store_node(branch)
+                    TrieNode::Branch(children, branch_value)
                 } else {
                     let idx = path[0] as usize;
-                    let child_hash = children[idx].unwrap_or_else(|| keccak256(&[]));
-                    let new_child = self.insert_at(&path[1..], value, child_hash);
-                    children[idx] = Some(new_child);
-                    let branch = Node::Branch(children, branch_value);
-                    self.store_node(branch)
+                    let child_node = match children[idx].take() {
+                        Some(child) => self.resolve(&child)?,
+                        None => TrieNode::Empty,
+                    };
+                    let new_child = self.insert_at(&path[1..], value, child_node)?;
+                    children[idx] = Some(dirty(new_child));
+                    TrieNode::Branch(children, branch_value)
                 }
             }
-        }
+        })
     }
     
     /// Helper: creates a branch from two diverging paths
     fn create_branch_from_divergence(
-        &mut self,
         path1: &[u8],
         value1: Vec<u8>,
         path2: &[u8],
         value2: Vec<u8>,
-    ) -> Hash {
-        let mut branch = Node::new_branch();
+    ) -> TrieNode {
+        let mut branch = TrieNode::new_branch();
         
-        // Handle first path
-        if path1.is_empty() {
-            if let Node::Branch(_, ref mut branch_value) = branch {
-                *branch_value = Some(value1);
-            }
-        } else {
-            let leaf1 = if path1.len() == 1 {
-                Node::new_leaf(&[], value1)
-            } else {
-                Node::new_leaf(&path1[1..], value1)
-            };
-            let leaf1_hash = self.store_node(leaf1);
-            if let Node::Branch(ref mut children, _) = branch {
-                children[path1[0] as usize] = Some(leaf1_hash);
-            }
-        }
-        
-        // Handle second path
-        if path2.is_empty() {
-            if let Node::Branch(_, ref mut branch_value) = branch {
-                *branch_value = Some(value2);
-            }
-        } else {
-            let leaf2 = if path2.len() == 1 {
-                Node::new_leaf(&[], value2)
+        for (path, value) in [(path1, value1), (path2, value2)] {
+            if path.is_empty() {
+                if let TrieNode::Branch(_, ref mut branch_value) = branch {
+                    *branch_value = Some(value);
+                }
             } else {
-                Node::new_leaf(&path2[1..], value2)
-            };
-            let leaf2_hash = self.store_node(leaf2);
-            if let Node::Branch(ref mut children, _) = branch {
-                children[path2[0] as usize] = Some(leaf2_hash);
+                let leaf = TrieNode::new_leaf(&path[1..], value);
+                if let TrieNode::Branch(ref mut children, _) = branch {
+                    children[path[0] as usize] = Some(dirty(leaf));
+                }
             }
         }
         
-        self.store_node(branch)
+        branch
     }
     
-    /// Helper: retrieves a value at a specific node
-    fn get_at(&self, path: &[u8], node_hash: Hash) -> Option<Vec<u8>> {
-        let node = self.get_node(node_hash)?;
-        
+    /// Helper: retrieves a value below a specific node
+    fn get_at(&self, path: &[u8], node: TrieNode) -> Result<Option<Vec<u8>>, MptError> {
         match node {
-            Node::Empty => None,
+            TrieNode::Empty => Ok(None),
             
-            Node::Leaf(encoded_path, value) => {
-                let (leaf_path, _) = compact_decode(&encoded_path);
+            TrieNode::Leaf(encoded_path, value) => {
+                let (leaf_path, _) = compact_decode(&encoded_path)?;
                 if leaf_path == path {
-                    Some(value)
+                    Ok(Some(value))
                 } else {
-                    None
+                    Ok(None)
                 }
             }
             
-            Node::Extension(encoded_path, child_hash) => {
-                let (ext_path, _) = compact_decode(&encoded_path);
+            TrieNode::Extension(encoded_path, child) => {
+                let (ext_path, _) = compact_decode(&encoded_path)?;
                 if path.len() < ext_path.len() || &path[..ext_path.len()] != ext_path.as_slice() {
-                    None
+                    Ok(None)
                 } else {
-                    self.get_at(&path[ext_path.len()..], child_hash)
+                    self.get_at(&path[ext_path.len()..], self.resolve(&child)?)
                 }
             }
             
-            Node::Branch(children, branch_value) => {
+            TrieNode::Branch(children, branch_value) => {
                 if path.is_empty() {
-                    branch_value
+                    Ok(branch_value)
                 } else {
                     let idx = path[0] as usize;
-                    children[idx].and_then(|child_hash| self.get_at(&path[1..], child_hash))
+                    match children[idx].as_ref() {
+                        Some(child) => self.get_at(&path[1..], self.resolve(child)?),
+                        None => Ok(None),
+                    }
                 }
             }
         }
     }
     
-    /// Helper: deletes a key at a specific node
-    fn delete_at(&mut self, path: &[u8], node_hash: Hash) -> Hash {
-        let node = match self.get_node(node_hash) {
-            Some(n) => n,
-            None => return keccak256(&[]), // Empty node
+    /// Helper: collects the encoded nodes on the path to a key, following `get_at`
+    ///
+    /// Inlined nodes are already part of their parent's encoding, so only
+    /// hash-referenced nodes are added to the proof.
+    fn prove_at(&self, path: &[u8], child: &Link, proof: &mut Vec<Vec<u8>>) -> Result<(), MptError> {
+        let node = match self.resolve(child)? {
+            TrieNode::Empty => return Ok(()),
+            n => n,
         };
+        match child {
+            Link::Stored(ChildRef::Hash(_)) => proof.push(node.encode_raw()),
+            Link::Stored(ChildRef::Inline(_)) => {}
+            Link::Dirty(_) => {
+                // Hashed once committed if large enough; the root (the first
+                // node visited) always is
+                let encoded = node.encode_raw();
+                if encoded.len() >= 32 || proof.is_empty() {
+                    proof.push(encoded);
+                }
+            }
+        }
         
         match node {
-            Node::Empty => keccak256(&[]),
+            TrieNode::Extension(encoded_path, child) => {
+                let (ext_path, _) = compact_decode(&encoded_path)?;
+                if path.starts_with(&ext_path) {
+                    self.prove_at(&path[ext_path.len()..], &child, proof)?;
+                }
+            }
+            
+            TrieNode::Branch(children, _) => {
+                if let Some((&idx, rest)) = path.split_first()
+                    && let Some(child) = &children[idx as usize]
+                {
+                    self.prove_at(rest, child, proof)?;
+                }
+            }
+            
+            // Leaves end the walk whether or not the path matches
+            TrieNode::Empty | TrieNode::Leaf(..) => {}
+        }
+        Ok(())
+    }
+    
+    /// Helper: deletes a key below `node` and returns the updated node
+    ///
+    /// Returns `None` if the key is absent, so that callers keep the node
+    /// they already hold instead of marking it as changed.
+    pub(crate) fn delete_at(&self, path: &[u8], node: TrieNode) -> Result<Option<TrieNode>, MptError> {
+        match node {
+            TrieNode::Empty => Ok(None),
             
-            Node::Leaf(encoded_path, _) => {
-                let (leaf_path, _) = compact_decode(&encoded_path);
+            TrieNode::Leaf(ref encoded_path, _) => {
+                let (leaf_path, _) = compact_decode(encoded_path)?;
                 if leaf_path == path {
                     // Delete this leaf
-                    keccak256(&[])
+                    Ok(Some(TrieNode::Empty))
                 } else {
-                    // Key not found, keep the leaf
-                    node_hash
+                    // Key not found
+                    Ok(None)
                 }
             }
             
-            Node::Extension(encoded_path, child_hash) => {
-                let (ext_path, _) = compact_decode(&encoded_path);
+            TrieNode::Extension(encoded_path, child) => {
+                let (ext_path, _) = compact_decode(&encoded_path)?;
                 if path.len() < ext_path.len() || &path[..ext_path.len()] != ext_path.as_slice() {
-                    // Path doesn't match, keep the extension
-                    node_hash
-                } else {
-                    let new_child = self.delete_at(&path[ext_path.len()..], child_hash);
-                    let empty_hash = keccak256(&[]);
-                    
-                    if new_child == empty_hash {
-                        // Child was deleted
-                        empty_hash
-                    } else {
-                        // Update extension
-                        let ext = Node::new_extension(&ext_path, new_child);
-                        self.store_node(ext)
-                    }
+                    // Path doesn't match
+                    return Ok(None);
+                }
+                let child_node = self.resolve(&child)?;
+                match self.delete_at(&path[ext_path.len()..], child_node)? {
+                    None => Ok(None),
+                    // Child was deleted
+                    Some(TrieNode::Empty) => Ok(Some(TrieNode::Empty)),
+                    // Update extension, merging paths if the child is no
+                    // longer a branch
+                    Some(new_child) => self.prepend_path(&ext_path, new_child).map(Some),
                 }
             }
             
-            Node::Branch(mut children, branch_value) => {
+            TrieNode::Branch(mut children, branch_value) => {
                 if path.is_empty() {
-                    // Delete value at branch
-                    let branch = Node::Branch(children, None);
-                    self.normalize_branch(branch)
-                } else {
-                    let idx = path[0] as usize;
-                    if let Some(child_hash) = children[idx] {
-                        let new_child = self.delete_at(&path[1..], child_hash);
-                        let empty_hash = keccak256(&[]);
-                        
-                        if new_child == empty_hash {
-                            children[idx] = None;
-                        } else {
-                            children[idx] = Some(new_child);
-                        }
+                    if branch_value.is_none() {
+                        return Ok(None);
                     }
-                    
-                    let branch = Node::Branch(children, branch_value);
-                    self.normalize_branch(branch)
+                    // Delete value at branch
+                    let branch = TrieNode::Branch(children, None);
+                    return self.normalize_branch(branch).map(Some);
                 }
+                
+                let idx = path[0] as usize;
+                let Some(child) = &children[idx] else {
+                    return Ok(None);
+                };
+                let child_node = self.resolve(child)?;
+                children[idx] = match self.delete_at(&path[1..], child_node)? {
+                    None => return Ok(None),
+                    Some(TrieNode::Empty) => None,
+                    Some(new_child) => Some(dirty(new_child)),
+                };
+                
+                let branch = TrieNode::Branch(children, branch_value);
+                self.normalize_branch(branch).map(Some)
             }
         }
     }
     
     /// Helper: normalizes a branch node (converts to simpler form if possible)
-    fn normalize_branch(&mut self, node: Node) -> Hash {
-        if let Node::Branch(children, branch_value) = node {
+    ///
+    /// A branch must hold at least two entries (children or value). With a
+    /// single child it collapses into that child, with only a value it becomes
+    /// a leaf, and with nothing left it disappears.
+    pub(crate) fn normalize_branch(&self, node: TrieNode) -> Result<TrieNode, MptError> {
+        if let TrieNode::Branch(mut children, branch_value) = node {
             let child_count: usize = children.iter().filter(|c| c.is_some()).count();
             
-            if child_count == 0 && branch_value.is_none() {
-                // Empty branch
-                return keccak256(&[]);
+            match (child_count, branch_value) {
+                (0, None) => {
+                    // Empty branch
+                    Ok(TrieNode::Empty)
+                }
+                (0, Some(value)) => {
+                    // Only the value is left
+                    Ok(TrieNode::new_leaf(&[], value))
+                }
+                (1, None) => {
+                    // Single child, merge it with the branch's slot nibble
+                    let idx = children.iter().position(|c| c.is_some()).unwrap();
+                    let child = children[idx].take().unwrap();
+                    let child_node = self.resolve(&child)?;
+                    self.prepend_path(&[idx as u8], child_node)
+                }
+                (_, branch_value) => {
+                    // Keep as branch
+                    Ok(TrieNode::Branch(children, branch_value))
+                }
             }
-            
-            if child_count == 1 && branch_value.is_none() {
-                // Single child, convert to extension or return child
-                let (idx, child_hash) = children
-                    .iter()
-                    .enumerate()
-                    .find(|(_, c)| c.is_some())
-                    .map(|(i, c)| (i, c.unwrap()))
-                    .unwrap();
-                
-                // Try to merge with child if it's an extension or leaf
-                if let Some(child_node) = self.get_node(child_hash) {
-                    match child_node {
-                        Node::Extension(encoded_path, grandchild_hash) => {
-                            let (ext_path, _) = compact_decode(&encoded_path);
-                            let mut new_path = vec![idx as u8];
-                            new_path.extend_from_slice(&ext_path);
-                            let ext = Node::new_extension(&new_path, grandchild_hash);
-                            return self.store_node(ext);
-                        }
-                        Node::Leaf(encoded_path, value) => {
-                            let (leaf_path, _) = compact_decode(&encoded_path);
-                            let mut new_path = vec![idx as u8];
-                            new_path.extend_from_slice(&leaf_path);
-                            let leaf = Node::new_leaf(&new_path, value);
-                            return self.store_node(leaf);
-                        }
-                        _ => {}
+        } else {
+            Ok(node)
+        }
+    }
+    
+    /// Helper: prefixes `node` with `prefix` nibbles
+    ///
+    /// Leaves and extensions absorb the prefix into their own path so that no
+    /// extension ever points at another extension or a leaf; a branch gets a
+    /// new extension in front of it.
+    pub(crate) fn prepend_path(&self, prefix: &[u8], node: TrieNode) -> Result<TrieNode, MptError> {
+        Ok(match node {
+            TrieNode::Empty => TrieNode::Empty,
+            TrieNode::Leaf(encoded_path, value) => {
+                let (leaf_path, _) = compact_decode(&encoded_path)?;
+                TrieNode::new_leaf(&[prefix, &leaf_path].concat(), value)
+            }
+            TrieNode::Extension(encoded_path, child) => {
+                let (ext_path, _) = compact_decode(&encoded_path)?;
+                TrieNode::new_extension(&[prefix, &ext_path].concat(), child)
+            }
+            TrieNode::Branch(..) => {
+                let branch_ref = dirty(node);
+                TrieNode::new_extension(prefix, branch_ref)
+            }
+        })
+    }
+    
+    /// Helper: commits the dirty children of `node`, returning it with every
+    /// child hashed or inlined
+    fn commit_children(&mut self, node: TrieNode) -> Result<Node, MptError> {
+        Ok(match node {
+            TrieNode::Empty => Node::Empty,
+            TrieNode::Leaf(path, value) => Node::Leaf(path, value),
+            TrieNode::Extension(path, child) => Node::Extension(path, self.commit_child(child)?),
+            TrieNode::Branch(children, value) => {
+                let mut committed: Box<[Option<ChildRef>; 16]> = Box::default();
+                for (slot, child) in committed.iter_mut().zip(*children) {
+                    if let Some(child) = child {
+                        *slot = Some(self.commit_child(child)?);
                     }
                 }
-                
-                // Just create an extension to the child
-                let ext = Node::new_extension(&[idx as u8], child_hash);
-                return self.store_node(ext);
+                Node::Branch(committed, value)
             }
-            
-            // Keep as branch
-            let branch = Node::Branch(children, branch_value);
-            self.store_node(branch)
-        } else {
-            self.store_node(node)
+        })
+    }
+    
+    /// Helper: commits a child reference, storing it if it is dirty
+    fn commit_child(&mut self, child: Link) -> Result<ChildRef, MptError> {
+        match child {
+            Link::Dirty(node) => {
+                let node = self.commit_children(Arc::unwrap_or_clone(node))?;
+                self.store_node(node)
+            }
+            Link::Stored(child) => Ok(child),
         }
     }
     
-    /// Stores a node and returns its hash
-    fn store_node(&mut self, node: Node) -> Hash {
-        let hash = node.hash();
-        self.storage.insert(hash, node);
-        hash
+    /// Stores a node and returns the reference its parent should hold
+    ///
+    /// Nodes whose encoding is shorter than 32 bytes are inlined rather than
+    /// stored.
+    fn store_node(&mut self, node: Node) -> Result<ChildRef, MptError> {
+        let encoded = node.encode_raw();
+        if encoded.len() < 32 {
+            return Ok(ChildRef::Inline(Box::new(node)));
+        }
+        let hash = keccak256(&encoded);
+        self.track_new_node(hash, &node)?;
+        self.storage.put(hash, encoded)?;
+        Ok(ChildRef::Hash(hash))
     }
     
     /// Retrieves a node by hash
-    fn get_node(&self, hash: Hash) -> Option<Node> {
-        let empty_hash = keccak256(&[]);
-        if hash == empty_hash {
-            return Some(Node::Empty);
+    fn get_node(&self, hash: Hash) -> Result<Node, MptError> {
+        if hash == EMPTY_ROOT {
+            return Ok(Node::Empty);
+        }
+        let encoded = self.storage.get(&hash)?.ok_or(MptError::MissingNode(hash))?;
+        Ok(Node::decode_raw(&encoded)?)
+    }
+    
+    /// Resolves a child reference to the node it points at
+    pub(crate) fn resolve(&self, child: &Link) -> Result<TrieNode, MptError> {
+        match child {
+            Link::Stored(ChildRef::Hash(hash)) => Ok(self.get_node(*hash)?.into()),
+            Link::Stored(ChildRef::Inline(node)) => Ok((**node).clone().into()),
+            Link::Dirty(node) => Ok((**node).clone()),
+        }
+    }
+    
+    /// Returns a reference to the root node
+    pub(crate) fn root_ref(&self) -> Link {
+        match &self.dirty_root {
+            Some(node) => Link::Dirty(node.clone()),
+            None => Link::stored(self.root),
         }
-        self.storage.get(&hash).cloned()
     }
+    
+    /// Returns the root node
+    pub(crate) fn root_node(&self) -> Result<TrieNode, MptError> {
+        self.resolve(&self.root_ref())
+    }
+    
+    /// Makes `node` the new, uncommitted root
+    ///
+    /// The committed root only changes in `commit`, so an emptied trie is
+    /// held as an `Empty` dirty root until then.
+    pub(crate) fn set_root(&mut self, node: TrieNode) {
+        if node == TrieNode::Empty && self.root == EMPTY_ROOT {
+            // Back to the committed state: nothing left to commit
+            self.dirty_root = None;
+        } else {
+            self.dirty_root = Some(Arc::new(node));
+        }
+    }
+}
+
+/// Helper: wraps a modified node in the reference its parent holds until the
+/// next commit
+pub(crate) fn dirty(node: TrieNode) -> Link {
+    Link::Dirty(Arc::new(node))
 }
 
 impl Default for MerklePatriciaTrie {
@@ -386,97 +784,106 @@ impl Default for MerklePatriciaTrie {
     }
 }
 
-impl MerklePatriciaTrie {
+impl<D: NodeDb> MerklePatriciaTrie<D> {
     /// Pretty prints the entire trie structure as a tree
     pub fn print_tree(&self) {
         println!("╔═══════════════════════════════════════════════════════════════");
         println!("║ Merkle Patricia Trie Structure");
         println!("╠═══════════════════════════════════════════════════════════════");
-        println!("║ Root Hash: 0x{}", hex_truncated(&self.root));
+        println!("║ Root Hash: 0x{}", hex_truncated(&self.root_hash()));
         println!("╚═══════════════════════════════════════════════════════════════\n");
         
-        let empty_hash = keccak256(&[]);
-        if self.root == empty_hash {
+        if self.root_hash() == EMPTY_ROOT {
             println!("  (empty trie)");
             return;
         }
         
-        self.print_node(self.root, "", true, "");
+        self.print_node(&self.root_ref(), "", true, "");
     }
     
     /// Helper function to recursively print a node and its children
-    fn print_node(&self, node_hash: Hash, prefix: &str, is_last: bool, path_so_far: &str) {
-        let node = match self.get_node(node_hash) {
-            Some(n) => n,
-            None => {
-                println!("{}{}── [MISSING NODE]", prefix, if is_last { "└" } else { "├" });
+    fn print_node(&self, child: &Link, prefix: &str, is_last: bool, path_so_far: &str) {
+        let node = match self.resolve(child) {
+            Ok(n) => n,
+            Err(err) => {
+                println!("{}{}── [{}]", prefix, if is_last { "└" } else { "├" }, err);
                 return;
             }
         };
         
         let branch = if is_last { "└──" } else { "├──" };
         let extension = if is_last { "    " } else { "│   " };
+        let hash_str = match child {
+            Link::Stored(child) => format_child_ref(child),
+            Link::Dirty(_) => String::from("(uncommitted)"),
+        };
         
         match node {
-            Node::Empty => {
+            TrieNode::Empty => {
                 println!("{}{} Empty", prefix, branch);
             }
             
-            Node::Leaf(encoded_path, value) => {
-                let (nibbles, _) = compact_decode(&encoded_path);
+            TrieNode::Leaf(encoded_path, value) => {
+                let (nibbles, _) = compact_decode(&encoded_path).unwrap_or_default();
                 let full_path = format!("{}{}", path_so_far, nibbles_to_hex(&nibbles));
                 let value_str = format_value(&value);
                 println!("{}{} Leaf", prefix, branch);
                 println!("{}{}   Path: {} → {}", prefix, extension, full_path, nibbles_to_hex(&nibbles));
                 println!("{}{}   Value: {}", prefix, extension, value_str);
-                println!("{}{}   Hash: 0x{}", prefix, extension, hex_truncated(&node_hash));
+                println!("{}{}   Hash: {}", prefix, extension, hash_str);
             }
             
-            Node::Extension(encoded_path, child_hash) => {
-                let (nibbles, _) = compact_decode(&encoded_path);
+            TrieNode::Extension(encoded_path, ext_child) => {
+                let (nibbles, _) = compact_decode(&encoded_path).unwrap_or_default();
                 let new_path = format!("{}{}", path_so_far, nibbles_to_hex(&nibbles));
                 println!("{}{} Extension", prefix, branch);
                 println!("{}{}   Path: {}", prefix, extension, nibbles_to_hex(&nibbles));
-                println!("{}{}   Hash: 0x{}", prefix, extension, hex_truncated(&node_hash));
+                println!("{}{}   Hash: {}", prefix, extension, hash_str);
                 
                 let new_prefix = format!("{}{}", prefix, extension);
-                self.print_node(child_hash, &new_prefix, true, &new_path);
+                self.print_node(&ext_child, &new_prefix, true, &new_path);
             }
             
-            Node::Branch(children, branch_value) => {
+            TrieNode::Branch(children, branch_value) => {
                 println!("{}{} Branch", prefix, branch);
                 if let Some(val) = branch_value {
                     println!("{}{}   Value: {}", prefix, extension, format_value(&val));
                 }
-                println!("{}{}   Hash: 0x{}", prefix, extension, hex_truncated(&node_hash));
+                println!("{}{}   Hash: {}", prefix, extension, hash_str);
                 
                 // Count non-empty children
-                let non_empty: Vec<(usize, Hash)> = children
+                let non_empty: Vec<(usize, &Link)> = children
                     .iter()
                     .enumerate()
-                    .filter_map(|(i, h)| h.map(|hash| (i, hash)))
+                    .filter_map(|(i, c)| c.as_ref().map(|child| (i, child)))
                     .collect();
                 
-                for (idx, (nibble, child_hash)) in non_empty.iter().enumerate() {
+                for (idx, (nibble, branch_child)) in non_empty.iter().enumerate() {
                     let is_last_child = idx == non_empty.len() - 1;
                     let new_path = format!("{}{:x}", path_so_far, nibble);
                     let new_prefix = format!("{}{}   ", prefix, extension);
                     
                     println!("{}{}[{:x}]", new_prefix, if is_last_child { "└" } else { "├" }, nibble);
                     let child_prefix = format!("{}{}   ", new_prefix, if is_last_child { " " } else { "│" });
-                    self.print_node(*child_hash, &child_prefix, true, &new_path);
+                    self.print_node(branch_child, &child_prefix, true, &new_path);
                 }
             }
         }
     }
     
+}
+
+impl MerklePatriciaTrie<MemoryDb> {
     /// Prints all nodes in storage with their details
     pub fn print_storage(&self) {
         println!("╔═══════════════════════════════════════════════════════════════");
         println!("║ Trie Storage Contents");
         println!("╠═══════════════════════════════════════════════════════════════");
         println!("║ Total nodes: {}", self.storage.len());
-        println!("║ Root hash: 0x{}", hex_truncated(&self.root));
+        println!("║ Root hash: 0x{}", hex_truncated(&self.root_hash()));
+        if self.is_dirty() {
+            println!("║ Uncommitted changes: not yet in storage, call commit()");
+        }
         println!("╚═══════════════════════════════════════════════════════════════\n");
         
         if self.storage.is_empty() {
@@ -484,27 +891,35 @@ impl MerklePatriciaTrie {
             return;
         }
         
-        for (idx, (hash, node)) in self.storage.iter().enumerate() {
+        for (idx, (hash, encoded)) in self.storage.iter().enumerate() {
             println!("Node #{}", idx + 1);
             println!("  Hash: 0x{}", hex_full(hash));
             
-            match node {
+            let node = match Node::decode_raw(encoded) {
+                Ok(node) => node,
+                Err(err) => {
+                    println!("  Type: [UNDECODABLE: {}]", err);
+                    println!();
+                    continue;
+                }
+            };
+            match &node {
                 Node::Empty => {
                     println!("  Type: Empty");
                 }
                 Node::Leaf(encoded_path, value) => {
-                    let (nibbles, _) = compact_decode(encoded_path);
+                    let (nibbles, _) = compact_decode(encoded_path).unwrap_or_default();
                     println!("  Type: Leaf");
                     println!("  Path (nibbles): {}", nibbles_to_hex(&nibbles));
                     println!("  Path (encoded): 0x{}", hex_bytes(encoded_path));
                     println!("  Value: {}", format_value(value));
                 }
                 Node::Extension(encoded_path, child_hash) => {
-                    let (nibbles, _) = compact_decode(encoded_path);
+                    let (nibbles, _) = compact_decode(encoded_path).unwrap_or_default();
                     println!("  Type: Extension");
                     println!("  Path (nibbles): {}", nibbles_to_hex(&nibbles));
                     println!("  Path (encoded): 0x{}", hex_bytes(encoded_path));
-                    println!("  Child: 0x{}", hex_truncated(child_hash));
+                    println!("  Child: {}", format_child_ref(child_hash));
                 }
                 Node::Branch(children, branch_value) => {
                     println!("  Type: Branch");
@@ -522,9 +937,9 @@ impl MerklePatriciaTrie {
                             .collect::<Vec<_>>()
                             .join(", "));
                     
-                    for (i, child_hash) in children.iter().enumerate() {
-                        if let Some(hash) = child_hash {
-                            println!("    [{:x}] → 0x{}", i, hex_truncated(hash));
+                    for (i, child) in children.iter().enumerate() {
+                        if let Some(child) = child {
+                            println!("    [{:x}] → {}", i, format_child_ref(child));
                         }
                     }
                 }
@@ -549,10 +964,10 @@ fn nibbles_to_hex(nibbles: &[u8]) -> String {
 /// Formats a value for display (shows as string if printable, otherwise as hex)
 fn format_value(value: &[u8]) -> String {
     // Try to display as UTF-8 string if it's valid
-    if let Ok(s) = std::str::from_utf8(value) {
-        if s.chars().all(|c| c.is_ascii_graphic() || c.is_ascii_whitespace()) {
-            return format!("\"{}\"", s);
-        }
+    if let Ok(s) = std::str::from_utf8(value)
+        && s.chars().all(|c| c.is_ascii_graphic() || c.is_ascii_whitespace())
+    {
+        return format!("\"{}\"", s);
     }
     // Otherwise show as hex
     format!("0x{}", hex_bytes(value))
@@ -575,6 +990,14 @@ fn hex_truncated(hash: &Hash) -> String {
     }
 }
 
+/// Formats a child reference (truncated hash, or a marker for inlined nodes)
+fn format_child_ref(child: &ChildRef) -> String {
+    match child {
+        ChildRef::Hash(hash) => format!("0x{}", hex_truncated(hash)),
+        ChildRef::Inline(_) => String::from("(inline)"),
+    }
+}
+
 /// Converts hash to full hex string
 fn hex_full(hash: &Hash) -> String {
     hex_bytes(hash)
@@ -583,50 +1006,52 @@ fn hex_full(hash: &Hash) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::proof::verify_proof;
+    use crate::test_utils::XorShift;
 
     #[test]
     fn test_insert_and_get() {
         let mut trie = MerklePatriciaTrie::new();
-        trie.insert(b"key1", b"value1".to_vec());
+        trie.insert(b"key1", b"value1".to_vec()).unwrap();
         
-        assert_eq!(trie.get(b"key1"), Some(b"value1".to_vec()));
-        assert_eq!(trie.get(b"key2"), None);
+        assert_eq!(trie.get(b"key1").unwrap(), Some(b"value1".to_vec()));
+        assert_eq!(trie.get(b"key2").unwrap(), None);
     }
 
     #[test]
     fn test_insert_update() {
         let mut trie = MerklePatriciaTrie::new();
-        trie.insert(b"key", b"value1".to_vec());
-        trie.insert(b"key", b"value2".to_vec());
+        trie.insert(b"key", b"value1".to_vec()).unwrap();
+        trie.insert(b"key", b"value2".to_vec()).unwrap();
         
-        assert_eq!(trie.get(b"key"), Some(b"value2".to_vec()));
+        assert_eq!(trie.get(b"key").unwrap(), Some(b"value2".to_vec()));
     }
 
     #[test]
     fn test_multiple_inserts() {
         let mut trie = MerklePatriciaTrie::new();
-        trie.insert(b"do", b"verb".to_vec());
-        trie.insert(b"dog", b"puppy".to_vec());
-        trie.insert(b"doge", b"coin".to_vec());
-        trie.insert(b"horse", b"stallion".to_vec());
+        trie.insert(b"do", b"verb".to_vec()).unwrap();
+        trie.insert(b"dog", b"puppy".to_vec()).unwrap();
+        trie.insert(b"doge", b"coin".to_vec()).unwrap();
+        trie.insert(b"horse", b"stallion".to_vec()).unwrap();
         
-        assert_eq!(trie.get(b"do"), Some(b"verb".to_vec()));
-        assert_eq!(trie.get(b"dog"), Some(b"puppy".to_vec()));
-        assert_eq!(trie.get(b"doge"), Some(b"coin".to_vec()));
-        assert_eq!(trie.get(b"horse"), Some(b"stallion".to_vec()));
+        assert_eq!(trie.get(b"do").unwrap(), Some(b"verb".to_vec()));
+        assert_eq!(trie.get(b"dog").unwrap(), Some(b"puppy".to_vec()));
+        assert_eq!(trie.get(b"doge").unwrap(), Some(b"coin".to_vec()));
+        assert_eq!(trie.get(b"horse").unwrap(), Some(b"stallion".to_vec()));
     }
 
     #[test]
     fn test_delete() {
         let mut trie = MerklePatriciaTrie::new();
-        trie.insert(b"key1", b"value1".to_vec());
-        trie.insert(b"key2", b"value2".to_vec());
+        trie.insert(b"key1", b"value1".to_vec()).unwrap();
+        trie.insert(b"key2", b"value2".to_vec()).unwrap();
         
-        assert_eq!(trie.get(b"key1"), Some(b"value1".to_vec()));
+        assert_eq!(trie.get(b"key1").unwrap(), Some(b"value1".to_vec()));
         
-        trie.delete(b"key1");
-        assert_eq!(trie.get(b"key1"), None);
-        assert_eq!(trie.get(b"key2"), Some(b"value2".to_vec()));
+        trie.delete(b"key1").unwrap();
+        assert_eq!(trie.get(b"key1").unwrap(), None);
+        assert_eq!(trie.get(b"key2").unwrap(), Some(b"value2".to_vec()));
     }
 
     #[test]
@@ -634,21 +1059,251 @@ mod tests {
         let mut trie = MerklePatriciaTrie::new();
         let initial_root = trie.root_hash();
         
-        trie.insert(b"key", b"value".to_vec());
+        trie.insert(b"key", b"value".to_vec()).unwrap();
         let root_after_insert = trie.root_hash();
         
         assert_ne!(initial_root, root_after_insert);
         
-        trie.delete(b"key");
+        trie.delete(b"key").unwrap();
         let root_after_delete = trie.root_hash();
         
         assert_eq!(initial_root, root_after_delete);
     }
 
+    #[test]
+    fn test_known_ethereum_roots() {
+        let mut trie = MerklePatriciaTrie::new();
+        trie.insert(b"do", b"verb".to_vec()).unwrap();
+        trie.insert(b"horse", b"stallion".to_vec()).unwrap();
+        trie.insert(b"doge", b"coin".to_vec()).unwrap();
+        trie.insert(b"dog", b"puppy".to_vec()).unwrap();
+        assert_eq!(
+            hex::encode(trie.root_hash()),
+            "5991bb8c6514148a29db676a14ac506cd2cd5775ace63c30a4fe457715e9ac84"
+        );
+
+        let mut trie = MerklePatriciaTrie::new();
+        trie.insert(b"doe", b"reindeer".to_vec()).unwrap();
+        trie.insert(b"dog", b"puppy".to_vec()).unwrap();
+        trie.insert(b"dogglesworth", b"cat".to_vec()).unwrap();
+        assert_eq!(
+            hex::encode(trie.root_hash()),
+            "8aad789dff2f538bca5d8ea56e8abe10f4c7ba3a5dea95fea4cd6e7c3a1168d3"
+        );
+    }
+
+    #[test]
+    fn test_small_nodes_are_inlined() {
+        let mut trie = MerklePatriciaTrie::new();
+        trie.insert(b"do", b"verb".to_vec()).unwrap();
+        trie.insert(b"dog", b"puppy".to_vec()).unwrap();
+        
+        // Every child fits inline, so storage only holds the committed root
+        let root = trie.commit().unwrap();
+        assert_eq!(trie.storage.len(), 1);
+        assert!(trie.storage.contains(&root).unwrap());
+        assert_eq!(trie.get(b"dog").unwrap(), Some(b"puppy".to_vec()));
+    }
+
+    #[test]
+    fn test_commit_defers_hashing() {
+        let mut trie = MerklePatriciaTrie::new();
+        for i in 0..100u8 {
+            trie.insert(&[i, i], vec![i; 40]).unwrap();
+        }
+        trie.delete(&[3, 3]).unwrap();
+        
+        // Nothing is stored yet, but the trie reads and proves as usual
+        assert!(trie.is_dirty());
+        assert_eq!(trie.storage.len(), 0);
+        assert_eq!(trie.get(&[7, 7]).unwrap(), Some(vec![7; 40]));
+        assert_eq!(trie.iter().count(), 99);
+        let root = trie.root_hash();
+        let proof = trie.prove(&[7, 7]).unwrap();
+        assert_eq!(verify_proof(root, &[7, 7], &proof), Ok(Some(vec![7; 40])));
+        
+        // Committing stores exactly the live nodes and keeps the root
+        assert_eq!(trie.commit().unwrap(), root);
+        assert!(!trie.is_dirty());
+        assert_eq!(trie.prune(&[]).unwrap(), 0);
+        
+        // Deleting absent keys leaves nothing to commit
+        for key in [&[3, 3][..], &[7], &[7, 7, 7], &[0xff, 0xff]] {
+            trie.delete(key).unwrap();
+        }
+        assert!(!trie.is_dirty());
+        assert_eq!(trie.prove(&[7, 7]).unwrap(), proof);
+        let reopened = MerklePatriciaTrie::with_db(trie.db().clone(), root);
+        assert_eq!(reopened.get(&[7, 7]).unwrap(), Some(vec![7; 40]));
+        assert_eq!(reopened.get(&[3, 3]).unwrap(), None);
+        
+        // Deleting everything leaves the committed root alone until the
+        // emptied trie is committed
+        for i in 0..100u8 {
+            trie.delete(&[i, i]).unwrap();
+        }
+        assert!(trie.is_dirty());
+        assert_eq!(trie.root_hash(), EMPTY_ROOT);
+        assert_eq!(trie.root, root);
+        assert_eq!(trie.commit().unwrap(), EMPTY_ROOT);
+        assert!(!trie.is_dirty());
+
+        // Deleting from an empty committed trie changes nothing
+        trie.delete(&[1, 1]).unwrap();
+        assert!(!trie.is_dirty());
+    }
+
+    #[test]
+    fn test_open_file_backed_trie() {
+        let path = std::env::temp_dir().join(format!("mpt-trie-open-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        
+        let root = {
+            let mut trie = MerklePatriciaTrie::open(&path, EMPTY_ROOT).unwrap();
+            trie.insert(b"do", b"verb".to_vec()).unwrap();
+            trie.insert(b"dog", b"puppy".to_vec()).unwrap();
+            trie.insert(b"horse", b"stallion".to_vec()).unwrap();
+            let root = trie.commit().unwrap();
+            trie.db().sync().unwrap();
+            root
+        };
+        
+        let mut trie = MerklePatriciaTrie::open(&path, root).unwrap();
+        assert_eq!(trie.get(b"dog").unwrap(), Some(b"puppy".to_vec()));
+        assert_eq!(trie.get(b"horse").unwrap(), Some(b"stallion".to_vec()));
+        trie.insert(b"doge", b"coin".to_vec()).unwrap();
+        assert_eq!(trie.get(b"doge").unwrap(), Some(b"coin".to_vec()));
+        
+        assert!(MerklePatriciaTrie::open(&path, [0x42; 32]).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_prune_removes_stale_nodes() {
+        let mut trie = MerklePatriciaTrie::new();
+        for i in 0..50u8 {
+            trie.insert(&[i], vec![i; 40]).unwrap();
+        }
+        let old_root = trie.commit().unwrap();
+        for i in 0..50u8 {
+            trie.insert(&[i], vec![i + 1; 40]).unwrap();
+            trie.commit().unwrap();
+        }
+        trie.delete(&[0]).unwrap();
+        trie.commit().unwrap();
+        
+        // Pruning while retaining the old root keeps both versions readable
+        let before = trie.db().len();
+        let removed = trie.prune(&[old_root]).unwrap();
+        assert!(removed > 0);
+        assert_eq!(trie.db().len(), before - removed);
+        assert_eq!(trie.get(&[7]).unwrap(), Some(vec![8; 40]));
+        let old = MerklePatriciaTrie::with_db(trie.db().clone(), old_root);
+        assert_eq!(old.get(&[7]).unwrap(), Some(vec![7; 40]));
+        
+        // Without it only the live nodes of the current root remain
+        trie.prune(&[]).unwrap();
+        let mut fresh = MerklePatriciaTrie::new();
+        for i in 1..50u8 {
+            fresh.insert(&[i], vec![i + 1; 40]).unwrap();
+        }
+        fresh.commit().unwrap();
+        assert_eq!(trie.db().len(), fresh.db().len());
+        for i in 1..50u8 {
+            assert_eq!(trie.get(&[i]).unwrap(), Some(vec![i + 1; 40]));
+        }
+    }
+
+    #[test]
+    fn test_missing_node_is_an_error() {
+        let mut trie = MerklePatriciaTrie::new();
+        trie.insert(b"dog", vec![1; 40]).unwrap();
+        trie.insert(b"horse", vec![2; 40]).unwrap();
+        trie.commit().unwrap();
+
+        // Drop the leaf holding "dog" from storage
+        let dog_leaf = keccak256(&Node::new_leaf(&bytes_to_nibbles(b"dog")[2..], vec![1; 40]).encode_raw());
+        trie.storage.remove(&dog_leaf).unwrap();
+
+        assert_eq!(trie.get(b"dog"), Err(MptError::MissingNode(dog_leaf)));
+        assert_eq!(trie.get(b"horse").unwrap(), Some(vec![2; 40]));
+        assert_eq!(trie.delete(b"dog"), Err(MptError::MissingNode(dog_leaf)));
+        assert!(trie.iter().any(|item| item.is_err()));
+
+        // A root that was never stored cannot be read
+        let unknown = MerklePatriciaTrie::with_db(MemoryDb::new(), [0x42; 32]);
+        assert_eq!(unknown.get(b"dog"), Err(MptError::MissingNode([0x42; 32])));
+    }
+
+    #[test]
+    fn test_delete_leaves_canonical_structure() {
+        let mut trie = MerklePatriciaTrie::new();
+        trie.insert(b"dog", b"puppy".to_vec()).unwrap();
+        trie.insert(b"doge", b"coin".to_vec()).unwrap();
+        trie.insert(b"dogs", b"pack".to_vec()).unwrap();
+        trie.delete(b"dogs").unwrap();
+        trie.delete(b"dog").unwrap();
+        
+        let mut fresh = MerklePatriciaTrie::new();
+        fresh.insert(b"doge", b"coin".to_vec()).unwrap();
+        assert_eq!(trie.root_hash(), fresh.root_hash());
+        assert!(matches!(trie.root_node(), Ok(TrieNode::Leaf(..))));
+    }
+
+    #[test]
+    fn test_random_histories_match_fresh_trie() {
+        use std::collections::BTreeMap;
+        
+        let mut rng = XorShift(0x9E37_79B9_7F4A_7C15);
+        for _ in 0..200 {
+            let mut trie = MerklePatriciaTrie::new();
+            let mut expected = BTreeMap::new();
+            
+            for _ in 0..40 {
+                // Short keys over a small alphabet force shared prefixes
+                let len = 1 + (rng.next() % 3) as usize;
+                let key: Vec<u8> = (0..len).map(|_| [0x00, 0x01, 0x10, 0xAB][(rng.next() % 4) as usize]).collect();
+                
+                if rng.next().is_multiple_of(3) {
+                    trie.delete(&key).unwrap();
+                    expected.remove(&key);
+                } else {
+                    let value = vec![(rng.next() % 256) as u8; 1 + (rng.next() % 40) as usize];
+                    trie.insert(&key, value.clone()).unwrap();
+                    expected.insert(key, value);
+                }
+            }
+            
+            let mut fresh = MerklePatriciaTrie::new();
+            for (key, value) in &expected {
+                fresh.insert(key, value.clone()).unwrap();
+            }
+            assert_eq!(trie.root_hash(), fresh.root_hash());
+            for (key, value) in &expected {
+                assert_eq!(trie.get(key).unwrap().as_ref(), Some(value));
+            }
+        }
+    }
+
     #[test]
     fn test_empty_trie() {
         let trie = MerklePatriciaTrie::new();
-        assert_eq!(trie.get(b"anything"), None);
+        assert_eq!(trie.get(b"anything").unwrap(), None);
+    }
+
+    #[test]
+    fn test_empty_root() {
+        let mut trie = MerklePatriciaTrie::new();
+        assert_eq!(
+            hex::encode(trie.root_hash()),
+            "56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421"
+        );
+        
+        trie.insert(b"a", b"1".to_vec()).unwrap();
+        trie.insert(b"b", b"2".to_vec()).unwrap();
+        trie.delete(b"a").unwrap();
+        trie.delete(b"b").unwrap();
+        assert_eq!(trie.root_hash(), EMPTY_ROOT);
     }
 }
 
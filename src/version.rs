@@ -0,0 +1,393 @@
+use crate::db::NodeDb;
+use crate::error::MptError;
+use crate::iter::TrieIter;
+use crate::node::{Hash, Link, Node, EMPTY_ROOT};
+use crate::proof::MerkleProof;
+use crate::trie::MerklePatriciaTrie;
+use std::collections::HashMap;
+
+/// How many labelled versions a trie keeps readable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Retention {
+    /// Keep every version
+    #[default]
+    All,
+    /// Keep the `n` most recently committed versions
+    ///
+    /// Committing a version beyond that drops the oldest one and removes the
+    /// nodes that only dropped versions referenced. Nodes still referenced by
+    /// a kept version, an open checkpoint, the current root, a root kept with
+    /// `retain_root` or any other stored node stay, so other roots sharing
+    /// the node store, such as storage tries, remain readable; only a root
+    /// that was itself a dropped version does not.
+    ///
+    /// Nodes are reference counted in memory for this, so each commit only
+    /// visits the nodes it stores or drops. Superseded nodes that no version
+    /// ever referenced, such as those of commits between versions, stay
+    /// until `prune`.
+    Latest(usize),
+}
+
+impl<D: NodeDb> MerklePatriciaTrie<D> {
+    /// Commits the trie and records the root under `label`, e.g. a block
+    /// number
+    ///
+    /// Recording a label again replaces its root. Returns the committed root.
+    pub fn commit_version(&mut self, label: u64) -> Result<Hash, MptError> {
+        let root = self.commit()?;
+        let replaced = self.versions.iter().position(|(existing, _)| *existing == label);
+        let replaced = replaced.and_then(|position| self.versions.remove(position));
+        self.versions.push_back((label, root));
+        self.hold(root);
+        if let Some((_, old_root)) = replaced {
+            self.release(old_root)?;
+        }
+        self.enforce_retention()?;
+        Ok(root)
+    }
+
+    /// Returns the root recorded under `label`, if it is still kept
+    pub fn version_root(&self, label: u64) -> Option<Hash> {
+        self.versions.iter().find(|(existing, _)| *existing == label).map(|(_, root)| *root)
+    }
+
+    /// Returns the kept versions as `(label, root)` pairs, oldest first
+    pub fn versions(&self) -> impl Iterator<Item = (u64, Hash)> + '_ {
+        self.versions.iter().copied()
+    }
+
+    /// Sets how many versions are kept, pruning any beyond the new limit
+    ///
+    /// Limiting retention on a trie that kept every version counts the
+    /// references of every stored node once.
+    pub fn set_retention(&mut self, retention: Retention) -> Result<(), MptError> {
+        self.refs = match retention {
+            Retention::All => None,
+            Retention::Latest(_) if self.refs.is_some() => self.refs.take(),
+            Retention::Latest(_) => Some(self.count_refs()?),
+        };
+        self.retention = retention;
+        self.enforce_retention()
+    }
+
+    /// Keeps `root` readable until `release_root`, even once no kept version
+    /// refers to it
+    pub fn retain_root(&mut self, root: Hash) {
+        self.retained.push(root);
+        self.hold(root);
+    }
+
+    /// Undoes one `retain_root` of `root`
+    ///
+    /// Under limited retention, the nodes nothing else refers to any more are
+    /// removed.
+    pub fn release_root(&mut self, root: Hash) -> Result<(), MptError> {
+        if let Some(position) = self.retained.iter().position(|kept| *kept == root) {
+            self.retained.swap_remove(position);
+            self.release(root)?;
+        }
+        Ok(())
+    }
+
+    /// Returns a read-only view of the trie as of `root`
+    ///
+    /// The root must be committed to this trie's node store and not pruned;
+    /// reads through a view of any other root fail with `MissingNode`.
+    pub fn view(&self, root: Hash) -> TrieView<'_, D> {
+        TrieView { trie: self, root }
+    }
+
+    /// Helper: drops the oldest versions beyond the retention limit and
+    /// removes the nodes only they referenced
+    fn enforce_retention(&mut self) -> Result<(), MptError> {
+        let Retention::Latest(keep) = self.retention else {
+            return Ok(());
+        };
+        if self.versions.len() <= keep {
+            return Ok(());
+        }
+        let excess = self.versions.len() - keep;
+        let dropped: Vec<_> = self.versions.drain(..excess).collect();
+        for (_, root) in dropped {
+            self.release(root)?;
+        }
+        Ok(())
+    }
+
+    /// Helper: counts a reference to `root` from a kept version, checkpoint
+    /// or retained root, while retention is limited
+    pub(crate) fn hold(&mut self, root: Hash) {
+        if let Some(refs) = &mut self.refs
+            && root != EMPTY_ROOT
+        {
+            *refs.entry(root).or_default() += 1;
+        }
+    }
+
+    /// Helper: drops a reference taken with `hold` and removes the nodes
+    /// left without references, except the current root
+    pub(crate) fn release(&mut self, root: Hash) -> Result<(), MptError> {
+        let Some(refs) = &mut self.refs else {
+            return Ok(());
+        };
+        let mut pending = vec![root];
+        while let Some(hash) = pending.pop() {
+            let Some(count) = refs.get_mut(&hash) else {
+                continue;
+            };
+            *count = count.saturating_sub(1);
+            // The current root stays readable even when nothing else refers
+            // to it
+            if *count > 0 || hash == self.root {
+                continue;
+            }
+            refs.remove(&hash);
+            if let Some(encoded) = self.storage.get(&hash)? {
+                pending.extend(Node::decode_raw(&encoded)?.child_hashes());
+                self.storage.remove(&hash)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Helper: counts the references a node about to be stored makes, unless
+    /// it is stored already
+    pub(crate) fn track_new_node(&mut self, hash: Hash, node: &Node) -> Result<(), MptError> {
+        let Some(refs) = &mut self.refs else {
+            return Ok(());
+        };
+        if !self.storage.contains(&hash)? {
+            for child in node.child_hashes() {
+                *refs.entry(child).or_default() += 1;
+            }
+            refs.entry(hash).or_default();
+        }
+        Ok(())
+    }
+
+    /// Helper: counts the references to every stored node from other stored
+    /// nodes, kept versions, open checkpoints and retained roots
+    pub(crate) fn count_refs(&self) -> Result<HashMap<Hash, usize>, MptError> {
+        let mut refs: HashMap<Hash, usize> = HashMap::new();
+        for hash in self.storage.hashes()? {
+            refs.entry(hash).or_default();
+            if let Some(encoded) = self.storage.get(&hash)? {
+                for child in Node::decode_raw(&encoded)?.child_hashes() {
+                    *refs.entry(child).or_default() += 1;
+                }
+            }
+        }
+        let versions = self.versions.iter().map(|(_, root)| root);
+        let checkpoints = self.checkpoints.iter().map(|checkpoint| &checkpoint.root);
+        for root in versions.chain(checkpoints).chain(&self.retained) {
+            if *root != EMPTY_ROOT {
+                *refs.entry(*root).or_default() += 1;
+            }
+        }
+        Ok(refs)
+    }
+}
+
+/// A read-only view of a trie as of an earlier root
+///
+/// Shares the trie's node store, so creating a view copies nothing. Reads see
+/// exactly the entries committed under `root`, regardless of later writes.
+pub struct TrieView<'a, D: NodeDb> {
+    trie: &'a MerklePatriciaTrie<D>,
+    root: Hash,
+}
+
+impl<'a, D: NodeDb> TrieView<'a, D> {
+    /// Returns the root this view reads from
+    pub fn root_hash(&self) -> Hash {
+        self.root
+    }
+
+    /// Retrieves a value by key
+    pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, MptError> {
+        self.trie.get_at_root(self.root, key)
+    }
+
+    /// Generates a Merkle proof for a key against this view's root
+    pub fn prove(&self, key: &[u8]) -> Result<MerkleProof, MptError> {
+        self.trie.prove_at_root(self.root, key)
+    }
+
+    /// Returns an iterator over all key/value pairs in lexicographic key order
+    pub fn iter(&self) -> TrieIter<'a, D> {
+        TrieIter::new(self.trie, Vec::new(), Link::stored(self.root))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDb;
+    use crate::proof::verify_proof;
+    use crate::test_utils::XorShift;
+    use std::cell::Cell;
+    use std::collections::BTreeMap;
+
+    /// A node store that counts full scans of its keys
+    #[derive(Default)]
+    struct CountingScans {
+        db: MemoryDb,
+        scans: Cell<usize>,
+    }
+
+    impl NodeDb for CountingScans {
+        fn get(&self, hash: &Hash) -> Result<Option<Vec<u8>>, MptError> {
+            self.db.get(hash)
+        }
+
+        fn put(&mut self, hash: Hash, encoded: Vec<u8>) -> Result<(), MptError> {
+            self.db.put(hash, encoded)
+        }
+
+        fn remove(&mut self, hash: &Hash) -> Result<(), MptError> {
+            self.db.remove(hash)
+        }
+
+        fn hashes(&self) -> Result<Vec<Hash>, MptError> {
+            self.scans.set(self.scans.get() + 1);
+            self.db.hashes()
+        }
+    }
+
+    /// Commits one version per block, each updating `balance` and adding a
+    /// key of its own
+    fn versioned_trie(blocks: u64) -> MerklePatriciaTrie {
+        let mut trie = MerklePatriciaTrie::new();
+        for block in 1..=blocks {
+            trie.insert(b"balance", vec![block as u8; 40]).unwrap();
+            trie.insert(&block.to_be_bytes(), vec![0xAB; 40]).unwrap();
+            trie.commit_version(block).unwrap();
+        }
+        trie
+    }
+
+    #[test]
+    fn test_read_as_of_version() {
+        let mut trie = versioned_trie(5);
+        trie.insert(b"balance", b"uncommitted".to_vec()).unwrap();
+
+        let root = trie.version_root(3).unwrap();
+        assert_eq!(trie.get_at_root(root, b"balance").unwrap(), Some(vec![3; 40]));
+        assert_eq!(trie.get_at_root(root, &4u64.to_be_bytes()).unwrap(), None);
+        assert_eq!(trie.get(b"balance").unwrap(), Some(b"uncommitted".to_vec()));
+        assert_eq!(trie.version_root(9), None);
+        assert_eq!(trie.versions().map(|(label, _)| label).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+
+        let view = trie.view(root);
+        assert_eq!(view.root_hash(), root);
+        assert_eq!(view.iter().count(), 4);
+        let proof = view.prove(b"balance").unwrap();
+        assert_eq!(verify_proof(root, b"balance", &proof), Ok(Some(vec![3; 40])));
+
+        // Relabelling replaces the earlier root
+        let latest = trie.commit_version(3).unwrap();
+        assert_eq!(trie.version_root(3), Some(latest));
+        assert_eq!(trie.versions().map(|(label, _)| label).collect::<Vec<_>>(), vec![1, 2, 4, 5, 3]);
+    }
+
+    #[test]
+    fn test_retention_prunes_old_versions() {
+        let mut trie = versioned_trie(3);
+        let first = trie.version_root(1).unwrap();
+        trie.set_retention(Retention::Latest(2)).unwrap();
+        assert_eq!(trie.version_root(1), None);
+        assert!(matches!(trie.get_at_root(first, b"balance"), Err(MptError::MissingNode(_))));
+
+        for block in 4..=10u64 {
+            trie.insert(b"balance", vec![block as u8; 40]).unwrap();
+            trie.commit_version(block).unwrap();
+        }
+        assert_eq!(trie.versions().map(|(label, _)| label).collect::<Vec<_>>(), vec![9, 10]);
+        let root = trie.version_root(9).unwrap();
+        assert_eq!(trie.view(root).get(b"balance").unwrap(), Some(vec![9; 40]));
+
+        // Nothing but the kept versions and the current root was left behind
+        assert_eq!(trie.prune(&[]).unwrap(), 0);
+        assert_eq!(trie.view(root).get(b"balance").unwrap(), Some(vec![9; 40]));
+    }
+
+    #[test]
+    fn test_view_of_empty_root() {
+        let trie = versioned_trie(1);
+        let view = trie.view(EMPTY_ROOT);
+        assert_eq!(view.get(b"balance").unwrap(), None);
+        assert_eq!(view.iter().count(), 0);
+        assert!(matches!(trie.view([0x42; 32]).get(b"balance"), Err(MptError::MissingNode(_))));
+    }
+
+    #[test]
+    fn test_retention_keeps_other_roots() {
+        let mut trie = MerklePatriciaTrie::new();
+        trie.set_retention(Retention::Latest(1)).unwrap();
+        let mut storage_roots = vec![EMPTY_ROOT];
+        let mut retained = EMPTY_ROOT;
+        for block in 1..=5u64 {
+            // A storage trie committed to the same store, as `StateTrie` does
+            let state = trie.root_state();
+            trie.set_root_hash(*storage_roots.last().unwrap());
+            trie.insert(&block.to_be_bytes(), vec![block as u8; 40]).unwrap();
+            storage_roots.push(trie.commit().unwrap());
+            trie.restore_root(state);
+
+            trie.insert(b"storage", storage_roots.last().unwrap().to_vec()).unwrap();
+            trie.insert(b"balance", vec![block as u8; 40]).unwrap();
+            trie.commit_version(block).unwrap();
+            if block == 2 {
+                retained = trie.version_root(2).unwrap();
+                trie.retain_root(retained);
+            }
+        }
+        assert_eq!(trie.versions().map(|(label, _)| label).collect::<Vec<_>>(), vec![5]);
+        for (count, root) in storage_roots.iter().enumerate() {
+            assert_eq!(trie.view(*root).iter().count(), count);
+        }
+
+        // A retained root outlives its version until released
+        assert_eq!(trie.view(retained).get(b"balance").unwrap(), Some(vec![2; 40]));
+        trie.release_root(retained).unwrap();
+        assert!(matches!(trie.view(retained).get(b"balance"), Err(MptError::MissingNode(_))));
+        assert_eq!(trie.view(storage_roots[5]).iter().count(), 5);
+    }
+
+    #[test]
+    fn test_retention_matches_history() {
+        let mut trie = MerklePatriciaTrie::with_db(CountingScans::default(), EMPTY_ROOT);
+        trie.set_retention(Retention::Latest(3)).unwrap();
+        let mut rng = XorShift(0x5EED_CAFE);
+        let mut expected = BTreeMap::new();
+        let mut history = Vec::new();
+        for block in 0..60u64 {
+            for _ in 0..4 {
+                // Few keys and values, so that earlier nodes come back
+                let key = [(rng.next() % 12) as u8; 3];
+                match rng.next() % 3 {
+                    0 => {
+                        trie.delete(&key).unwrap();
+                        expected.remove(key.as_slice());
+                    }
+                    _ => {
+                        let value = vec![(rng.next() % 3) as u8; 40];
+                        trie.insert(&key, value.clone()).unwrap();
+                        expected.insert(key.to_vec(), value);
+                    }
+                }
+            }
+            let root = trie.commit_version(block).unwrap();
+            history.push((root, expected.clone()));
+            for (root, entries) in history.iter().rev().take(3) {
+                let read: BTreeMap<_, _> = trie.view(*root).iter().map(Result::unwrap).collect();
+                assert_eq!(&read, entries);
+            }
+        }
+
+        // Only enabling retention scanned the whole store, and it left no
+        // garbage behind
+        assert_eq!(trie.db().scans.get(), 1);
+        assert_eq!(trie.prune(&[]).unwrap(), 0);
+    }
+}
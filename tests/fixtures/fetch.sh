@@ -0,0 +1,24 @@
+#!/bin/sh
+# Replaces the fixtures in this directory with the upstream ethereum/tests
+# TrieTests files, byte-for-byte, as of the given commit. Requires curl and
+# network access.
+#
+# Usage: ./fetch.sh <ethereum/tests commit hash>
+set -eu
+
+COMMIT="${1:?usage: $0 <ethereum/tests commit hash>}"
+BASE="https://raw.githubusercontent.com/ethereum/tests/$COMMIT/TrieTests"
+cd "$(dirname "$0")"
+
+for file in \
+    trietest.json \
+    trieanyorder.json \
+    hex_encoded_securetrie_test.json \
+    trietest_secureTrie.json \
+    trieanyorder_secureTrie.json
+do
+    curl -fsSL "$BASE/$file" -o "$file"
+    echo "fetched $file"
+done
+echo "$COMMIT" > UPSTREAM_COMMIT
+echo "pinned to ethereum/tests $COMMIT"